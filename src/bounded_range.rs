@@ -0,0 +1,262 @@
+//
+//   Copyright 2016 Andrew Hunter
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+//
+
+//!
+//! `SymbolRange` is always inclusive at both ends, so splitting two overlapping ranges needs to find the symbols just
+//! before and after the point where they overlap. This is why `fix_overlapping_ranges` requires symbols to be `Countable`.
+//!
+//! A `BoundedRange` marks each of its endpoints as either inclusive or exclusive. Where a `SymbolRange` would need to end
+//! on the symbol before `x`, a bounded range can instead end at `x` exclusively, so overlapping ranges can be split using
+//! only comparisons. This makes it possible to work with continuous symbol domains such as `f64`.
+//!
+//! Only `PartialOrd` is required so that floating point values can be used directly, but the symbols in the ranges must
+//! still be totally ordered amongst themselves (ie, ranges containing `NaN` are not supported).
+//!
+//! ```
+//! # use concordance::*;
+//! let pieces = BoundedRange::split_overlapping(&vec![BoundedRange::inclusive(0.0, 5.0), BoundedRange::inclusive(3.0, 10.0)]);
+//!
+//! // [0.0, 3.0), [3.0, 5.0], (5.0, 10.0]
+//! # assert!(pieces.len() == 3);
+//! ```
+//!
+
+use std::cmp::*;
+
+///
+/// One end of a bounded range
+///
+#[derive(Clone, PartialEq, Debug)]
+pub enum RangeBound<Symbol> {
+    /// The range includes this symbol
+    Inclusive(Symbol),
+
+    /// The range stops just short of this symbol
+    Exclusive(Symbol)
+}
+
+use self::RangeBound::*;
+
+impl<Symbol> RangeBound<Symbol> {
+    ///
+    /// Retrieves the symbol at this bound
+    ///
+    #[inline]
+    pub fn symbol(&self) -> &Symbol {
+        match self {
+            &Inclusive(ref symbol) => symbol,
+            &Exclusive(ref symbol) => symbol
+        }
+    }
+}
+
+///
+/// Represents a range of symbols where each end can be inclusive or exclusive
+///
+#[derive(Clone, PartialEq, Debug)]
+pub struct BoundedRange<Symbol> {
+    /// The lower end of the range
+    pub lowest: RangeBound<Symbol>,
+
+    /// The upper end of the range
+    pub highest: RangeBound<Symbol>
+}
+
+///
+/// A point between two symbols: either just before or just after a particular symbol
+///
+struct Cut<'a, Symbol: 'a> {
+    symbol: &'a Symbol,
+    after:  bool
+}
+
+impl<'a, Symbol: PartialOrd> Cut<'a, Symbol> {
+    ///
+    /// The cut where a range starts
+    ///
+    #[inline]
+    fn lower(bound: &'a RangeBound<Symbol>) -> Cut<'a, Symbol> {
+        match bound {
+            &Inclusive(ref symbol) => Cut { symbol: symbol, after: false },
+            &Exclusive(ref symbol) => Cut { symbol: symbol, after: true }
+        }
+    }
+
+    ///
+    /// The cut where a range finishes
+    ///
+    #[inline]
+    fn upper(bound: &'a RangeBound<Symbol>) -> Cut<'a, Symbol> {
+        match bound {
+            &Inclusive(ref symbol) => Cut { symbol: symbol, after: true },
+            &Exclusive(ref symbol) => Cut { symbol: symbol, after: false }
+        }
+    }
+
+    ///
+    /// Orders two cuts
+    ///
+    fn cmp(&self, other: &Cut<'a, Symbol>) -> Ordering {
+        let symbol_order = self.symbol.partial_cmp(other.symbol).unwrap_or(Ordering::Equal);
+
+        if symbol_order == Ordering::Equal {
+            self.after.cmp(&other.after)
+        } else {
+            symbol_order
+        }
+    }
+}
+
+impl<Symbol: PartialOrd+Clone> BoundedRange<Symbol> {
+    ///
+    /// Creates a new range including both of the specified symbols and everything in between
+    ///
+    #[inline]
+    pub fn inclusive(lowest: Symbol, highest: Symbol) -> BoundedRange<Symbol> {
+        BoundedRange { lowest: Inclusive(lowest), highest: Inclusive(highest) }
+    }
+
+    ///
+    /// Creates a new range with the specified bounds
+    ///
+    #[inline]
+    pub fn new(lowest: RangeBound<Symbol>, highest: RangeBound<Symbol>) -> BoundedRange<Symbol> {
+        BoundedRange { lowest: lowest, highest: highest }
+    }
+
+    ///
+    /// True if this range contains no symbols
+    ///
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        Cut::lower(&self.lowest).cmp(&Cut::upper(&self.highest)) != Ordering::Less
+    }
+
+    ///
+    /// True if the specified symbol is in this range
+    ///
+    pub fn includes(&self, symbol: &Symbol) -> bool {
+        let above_lowest = match self.lowest {
+            Inclusive(ref lowest) => symbol >= lowest,
+            Exclusive(ref lowest) => symbol > lowest
+        };
+        let below_highest = match self.highest {
+            Inclusive(ref highest) => symbol <= highest,
+            Exclusive(ref highest) => symbol < highest
+        };
+
+        above_lowest && below_highest
+    }
+
+    ///
+    /// True if every symbol in the specified range is also in this range
+    ///
+    pub fn contains_range(&self, range: &BoundedRange<Symbol>) -> bool {
+        Cut::lower(&self.lowest).cmp(&Cut::lower(&range.lowest)) != Ordering::Greater
+            && Cut::upper(&range.highest).cmp(&Cut::upper(&self.highest)) != Ordering::Greater
+    }
+
+    ///
+    /// True if this range has any symbols in common with another range
+    ///
+    pub fn overlaps(&self, range: &BoundedRange<Symbol>) -> bool {
+        Cut::lower(&self.lowest).cmp(&Cut::upper(&range.highest)) == Ordering::Less
+            && Cut::lower(&range.lowest).cmp(&Cut::upper(&self.highest)) == Ordering::Less
+    }
+
+    ///
+    /// Splits a set of possibly overlapping ranges into a sorted set of non-overlapping ranges
+    ///
+    /// Every range in the result is either entirely inside or entirely outside each of the original ranges, and the result
+    /// covers exactly the same symbols as the original ranges. Empty ranges are ignored.
+    ///
+    pub fn split_overlapping(ranges: &[BoundedRange<Symbol>]) -> Vec<BoundedRange<Symbol>> {
+        let ranges: Vec<_> = ranges.iter().filter(|range| !range.is_empty()).collect();
+
+        // Every range boundary is a point where the result might need to be split
+        let mut cuts = vec![];
+        for range in ranges.iter() {
+            cuts.push(Cut::lower(&range.lowest));
+            cuts.push(Cut::upper(&range.highest));
+        }
+
+        cuts.sort_by(|a, b| a.cmp(b));
+        cuts.dedup_by(|a, b| a.cmp(b) == Ordering::Equal);
+
+        // Generate the pieces between each pair of cuts, keeping the ones that are in one of the original ranges
+        let mut result = vec![];
+
+        for index in 1..cuts.len() {
+            let (start, end) = (&cuts[index-1], &cuts[index]);
+
+            let lowest  = if start.after { Exclusive(start.symbol.clone()) } else { Inclusive(start.symbol.clone()) };
+            let highest = if end.after { Inclusive(end.symbol.clone()) } else { Exclusive(end.symbol.clone()) };
+            let piece   = BoundedRange { lowest: lowest, highest: highest };
+
+            if ranges.iter().any(|range| range.contains_range(&piece)) {
+                result.push(piece);
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn can_split_overlapping_float_ranges() {
+        let pieces = BoundedRange::split_overlapping(&vec![BoundedRange::inclusive(0.0, 5.0), BoundedRange::inclusive(3.0, 10.0)]);
+
+        assert!(pieces == vec![
+            BoundedRange::new(Inclusive(0.0), Exclusive(3.0)),
+            BoundedRange::new(Inclusive(3.0), Inclusive(5.0)),
+            BoundedRange::new(Exclusive(5.0), Inclusive(10.0))
+        ]);
+    }
+
+    #[test]
+    fn split_ranges_do_not_overlap() {
+        let pieces = BoundedRange::split_overlapping(&vec![
+            BoundedRange::inclusive(0.0, 5.0),
+            BoundedRange::inclusive(3.0, 10.0),
+            BoundedRange::inclusive(5.0, 5.0),
+            BoundedRange::inclusive(20.0, 30.0)
+        ]);
+
+        for first in 0..pieces.len() {
+            for second in (first+1)..pieces.len() {
+                assert!(!pieces[first].overlaps(&pieces[second]));
+            }
+        }
+
+        assert!(pieces.iter().any(|piece| piece.includes(&5.0)));
+        assert!(pieces.iter().any(|piece| piece.includes(&25.0)));
+        assert!(!pieces.iter().any(|piece| piece.includes(&15.0)));
+    }
+
+    #[test]
+    fn exclusive_bounds_exclude_symbol() {
+        let range = BoundedRange::new(Exclusive(1.0), Exclusive(2.0));
+
+        assert!(!range.includes(&1.0));
+        assert!(range.includes(&1.5));
+        assert!(!range.includes(&2.0));
+        assert!(BoundedRange::new(Exclusive(1.0), Exclusive(1.0)).is_empty());
+    }
+}
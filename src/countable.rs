@@ -27,67 +27,139 @@
 
 use std::char;
 
+///
+/// The maximum number of steps the default implementation of `steps_between` will take before giving up
+///
+const MAX_STEPS_BETWEEN: usize = 65536;
+
 ///
 /// Trait implemented by types that can be counted
 ///
 pub trait Countable {
     fn next(&self) -> Self;
     fn prev(&self) -> Self;
+
+    ///
+    /// Returns the number of times `next()` needs to be called to get from `low` to `high`, or `None` if `high` can't be
+    /// reached from `low`
+    ///
+    /// The default implementation calls `next()` repeatedly, so it gives up and returns `None` if `high` isn't reached after a
+    /// large number of steps. The implementations for the integer types and `char` calculate the result directly.
+    ///
+    fn steps_between(low: &Self, high: &Self) -> Option<usize> where Self: Sized+PartialEq {
+        if low == high {
+            return Some(0);
+        }
+
+        let mut current = low.next();
+
+        for steps in 1..MAX_STEPS_BETWEEN {
+            if &current == high {
+                return Some(steps);
+            }
+
+            current = current.next();
+        }
+
+        None
+    }
+}
+
+///
+/// Converts the distance between two integers to a number of steps
+///
+#[inline]
+fn integer_steps_between(low: i128, high: i128) -> Option<usize> {
+    if high < low || high - low > usize::max_value() as i128 {
+        None
+    } else {
+        Some((high - low) as usize)
+    }
 }
 
 impl Countable for usize { 
     fn next(&self) -> Self { *self+1 }
     fn prev(&self) -> Self { *self-1 }
+    fn steps_between(low: &Self, high: &Self) -> Option<usize> { integer_steps_between(*low as i128, *high as i128) }
 }
 
 impl Countable for u8 { 
     fn next(&self) -> Self { *self+1 }
     fn prev(&self) -> Self { *self-1 }
+    fn steps_between(low: &Self, high: &Self) -> Option<usize> { integer_steps_between(*low as i128, *high as i128) }
 }
 
 impl Countable for u16 { 
     fn next(&self) -> Self { *self+1 }
     fn prev(&self) -> Self { *self-1 }
+    fn steps_between(low: &Self, high: &Self) -> Option<usize> { integer_steps_between(*low as i128, *high as i128) }
 }
 
 impl Countable for u32 { 
     fn next(&self) -> Self { *self+1 }
     fn prev(&self) -> Self { *self-1 }
+    fn steps_between(low: &Self, high: &Self) -> Option<usize> { integer_steps_between(*low as i128, *high as i128) }
 }
 
 impl Countable for isize { 
     fn next(&self) -> Self { *self+1 }
     fn prev(&self) -> Self { *self-1 }
+    fn steps_between(low: &Self, high: &Self) -> Option<usize> { integer_steps_between(*low as i128, *high as i128) }
 }
 
 impl Countable for i8 { 
     fn next(&self) -> Self { *self+1 }
     fn prev(&self) -> Self { *self-1 }
+    fn steps_between(low: &Self, high: &Self) -> Option<usize> { integer_steps_between(*low as i128, *high as i128) }
 }
 
 impl Countable for i16 { 
     fn next(&self) -> Self { *self+1 }
     fn prev(&self) -> Self { *self-1 }
+    fn steps_between(low: &Self, high: &Self) -> Option<usize> { integer_steps_between(*low as i128, *high as i128) }
 }
 
 impl Countable for i32 { 
     fn next(&self) -> Self { *self+1 }
     fn prev(&self) -> Self { *self-1 }
+    fn steps_between(low: &Self, high: &Self) -> Option<usize> { integer_steps_between(*low as i128, *high as i128) }
 }
 
 impl Countable for u64 { 
     fn next(&self) -> Self { *self+1 }
     fn prev(&self) -> Self { *self-1 }
+    fn steps_between(low: &Self, high: &Self) -> Option<usize> { integer_steps_between(*low as i128, *high as i128) }
 }
 
 impl Countable for i64 { 
     fn next(&self) -> Self { *self+1 }
     fn prev(&self) -> Self { *self-1 }
+    fn steps_between(low: &Self, high: &Self) -> Option<usize> { integer_steps_between(*low as i128, *high as i128) }
 }
 
 impl Countable for char { 
-    fn next(&self) -> Self { char::from_u32((*self as u32)+1).unwrap_or('\u{0000}') }
-    fn prev(&self) -> Self { char::from_u32((*self as u32)-1).unwrap_or('\u{ffff}') }
+    // Chars can't be surrogates, so next and prev skip over the surrogate range
+    fn next(&self) -> Self {
+        match *self as u32 {
+            0xd7ff  => '\u{e000}',
+            code    => char::from_u32(code+1).unwrap_or('\u{0000}')
+        }
+    }
+
+    fn prev(&self) -> Self {
+        match *self as u32 {
+            0xe000  => '\u{d7ff}',
+            code    => char::from_u32(code-1).unwrap_or('\u{ffff}')
+        }
+    }
+
+    fn steps_between(low: &Self, high: &Self) -> Option<usize> {
+        // Chars can't be surrogates, so don't count the surrogate range if the two characters are on either side of it
+        let surrogates  = if (*low as u32) < 0xd800 && (*high as u32) > 0xdfff { 0x800 } else { 0 };
+        let steps       = integer_steps_between(*low as i128, *high as i128)?;
+
+        Some(steps - surrogates)
+    }
 }
 
 #[cfg(test)]
@@ -158,6 +230,43 @@ mod test {
         assert!(val.prev() == 0);
     }
 
+    #[test]
+    fn next_prev_char_skip_surrogates() {
+        assert!('\u{d7ff}'.next() == '\u{e000}');
+        assert!('\u{e000}'.prev() == '\u{d7ff}');
+    }
+
+    #[test]
+    fn can_count_steps_between_chars() {
+        assert!(char::steps_between(&'a', &'d') == Some(3));
+        assert!(char::steps_between(&'d', &'a') == None);
+        assert!(char::steps_between(&'\u{d7ff}', &'\u{e000}') == Some(1));
+    }
+
+    #[test]
+    fn can_count_steps_between_integers() {
+        assert!(u32::steps_between(&5, &5) == Some(0));
+        assert!(u32::steps_between(&5, &9) == Some(4));
+        assert!(u32::steps_between(&9, &5) == None);
+        assert!(i8::steps_between(&-128, &127) == Some(255));
+        assert!(u64::steps_between(&10, &20) == Some(10));
+    }
+
+    #[test]
+    fn default_steps_between_walks_next() {
+        #[derive(PartialEq, Clone, Copy)]
+        struct Walker(u32);
+
+        impl Countable for Walker {
+            fn next(&self) -> Self { Walker(self.0 + 1) }
+            fn prev(&self) -> Self { Walker(self.0 - 1) }
+        }
+
+        assert!(Walker::steps_between(&Walker(2), &Walker(2)) == Some(0));
+        assert!(Walker::steps_between(&Walker(2), &Walker(7)) == Some(5));
+        assert!(Walker::steps_between(&Walker(7), &Walker(2)) == None);
+    }
+
     #[test]
     fn can_get_next_prev_char() {
         let val = 'b';
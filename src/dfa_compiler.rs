@@ -20,7 +20,7 @@
 //!
 //! The NDFA should not have any overlapping symbols, which is to say symbols that are not equal and yet could match the same
 //! input symbol. If the builder finds that two NDFA states have identical output symbols, then the builder will pick the symbol
-//! that compares as being lower as the final output symbol.
+//! that compares as being lower as the final output symbol. `build_with_resolver` can be used to supply a different rule.
 //!
 //! Any NDFA can be converted into a DFA: if the NDFA can move to two states as the result of a particular input symbol, the DFA
 //! just needs a single new state representing both those possible states. In this way, the NDFA can be converted into a form where
@@ -39,16 +39,25 @@
 //!
 
 use std::marker::PhantomData;
-use std::collections::HashSet;
 use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::iter::*;
 
 use super::dfa_builder::*;
 use super::state_machine::*;
+use super::countable::*;
+use super::symbol_range::*;
+use super::overlapping_symbols::*;
+use super::ndfa::*;
+use super::symbol_range_dfa::*;
 
 ///
 /// Builds a deterministic finite automaton from a NDFA
 ///
+/// The source state machine must not have any default transitions (see `StateMachine::default_transition_for_state()`):
+/// these can't be combined with the other transitions when building the DFA, so compiling a state machine that has them
+/// will panic.
+///
 pub struct DfaCompiler<InputSymbol: Ord+Clone, OutputSymbol, DfaType, Ndfa: StateMachine<InputSymbol, OutputSymbol>, Builder: DfaBuilder<InputSymbol, OutputSymbol, DfaType>> {
     /// State machine that is to be compiled
     ndfa: Ndfa,
@@ -71,12 +80,10 @@ struct DfaState {
 impl DfaState {
     /// Creates a DFA state from a set of source states
     fn create(source_states: Vec<StateId>) -> DfaState {
-        let mut set = HashSet::new();
-        for state in source_states {
-            set.insert(state);
-        }
+        let mut state = DfaState { source_states: source_states };
+        state.dedupe();
 
-        DfaState { source_states: Vec::from_iter(set.into_iter()) }
+        state
     }
 
     /// Removes any duplicate states from source_states
@@ -88,9 +95,6 @@ impl DfaState {
 
 /// Represents the transitions for a source state
 struct DfaTransitions<InputSymbol, OutputSymbol: Ord> {
-    /// ID of this state in the resulting DFA
-    state_id: StateId,
-
     /// Transitions for this state (will be unique if `merge_states` is called)
     transitions: Vec<(InputSymbol, DfaState)>,
 
@@ -140,14 +144,14 @@ impl<InputSymbol: Ord+Clone, OutputSymbol: Ord> DfaTransitions<InputSymbol, Outp
     ///
     /// Finds the output symbol that corresponds to this state
     ///
-    /// Rule is that if there is more than one output symbol then the symbol whose value is ordered lowest is the output for this state
+    /// If there is more than one output symbol, then the resolver is called to decide which one to use
     ///
-    fn output_symbol(&mut self) -> Option<&OutputSymbol> {
-        if self.output.len() > 0 {
-            self.output.sort();
-            Some(&self.output[0])
-        } else {
-            None
+    fn output_symbol<Resolver: Fn(&[OutputSymbol]) -> OutputSymbol>(&self, resolver: &Resolver) -> Option<OutputSymbol> 
+    where OutputSymbol: Clone {
+        match self.output.len() {
+            0 => None,
+            1 => Some(self.output[0].clone()),
+            _ => Some(resolver(&self.output))
         }
     }
 }
@@ -162,6 +166,14 @@ impl<InputSymbol: Ord+Clone, OutputSymbol: Ord+Clone, DfaType, Ndfa: StateMachin
         compiler.compile()
     }
 
+    ///
+    /// Builds a DFA using an NDFA and a builder, using a function to decide on the output symbol for states with more than one
+    ///
+    pub fn build_with_resolver<Resolver: Fn(&[OutputSymbol]) -> OutputSymbol>(ndfa: Ndfa, builder: Builder, resolver: Resolver) -> DfaType {
+        let compiler = DfaCompiler::new(ndfa, builder);
+        compiler.compile_with_resolver(resolver)
+    }
+
     ///
     /// Creates a new DFA compiler using a particular builder and NDFA
     ///
@@ -172,27 +184,56 @@ impl<InputSymbol: Ord+Clone, OutputSymbol: Ord+Clone, DfaType, Ndfa: StateMachin
     ///
     /// Compiles the NDFA into a DFA
     ///
+    /// Rule is that if there is more than one output symbol then the symbol whose value is ordered lowest is the output for this state
+    ///
     pub fn compile(self) -> DfaType {
+        self.compile_with_resolver(lowest_output)
+    }
+
+    ///
+    /// Compiles the NDFA into a DFA, using a function to decide on the output symbol for states with more than one
+    ///
+    /// The resolver is passed the distinct output symbols for a state and should return the one to use. The symbols are in
+    /// ascending order of the NDFA states they came from. When patterns are compiled into the NDFA one after another (as
+    /// `TokenMatcher` does), this is the order the patterns were added in, so a resolver of `|outputs| outputs[0].clone()`
+    /// will pick the output of the pattern that was added first.
+    ///
+    pub fn compile_with_resolver<Resolver: Fn(&[OutputSymbol]) -> OutputSymbol>(self, resolver: Resolver) -> DfaType {
+        let mut known_states = HashMap::new();
+        let mut to_process   = VecDeque::new();
+
+        self.compile_using(resolver, &mut known_states, &mut to_process)
+    }
+
+    ///
+    /// Compiles the NDFA into a DFA, using the supplied (empty) collections to track the states that have been generated
+    ///
+    /// States are processed breadth-first and are given IDs in the order they're discovered, so compiling the same NDFA
+    /// always produces the same DFA.
+    ///
+    fn compile_using<Resolver: Fn(&[OutputSymbol]) -> OutputSymbol>(self, resolver: Resolver, known_states: &mut HashMap<DfaState, StateId>, to_process: &mut VecDeque<DfaState>) -> DfaType {
         // We assume that input symbols are non-overlapping, which is not automatically the case for symbol ranges
         // You can call Ndfa.fix_overlapping_ranges() to remove any overlapping ranges from an NDFA
 
         // Work out the state mapping for each input symbol
         let mut states       = vec![];
-        let mut known_states = HashMap::new();
-        let mut to_process   = vec![];
 
         // All state machines have state 0 as their starting state
         let state_zero = DfaState::create(vec![0]);
 
         known_states.insert(state_zero.clone(), 0);
-        to_process.push(state_zero);
+        to_process.push_back(state_zero);
 
-        while let Some(state) = to_process.pop() {
+        while let Some(state) = to_process.pop_front() {
             // Create a new transitions object for this state
             let mut transitions = vec![];
             let mut output      = vec![];
 
             for source_state in &state.source_states {
+                if self.ndfa.default_transition_for_state(*source_state).is_some() {
+                    panic!("Cannot compile a state machine with default transitions into a DFA");
+                }
+
                 let source_transitions = self.ndfa.get_transitions_for_state(*source_state);
 
                 for (symbol, state) in source_transitions {
@@ -200,34 +241,39 @@ impl<InputSymbol: Ord+Clone, OutputSymbol: Ord+Clone, DfaType, Ndfa: StateMachin
                 }
 
                 if let Some(source_output) = self.ndfa.output_symbol_for_state(*source_state) {
-                    output.push(source_output.clone());
+                    if !output.contains(source_output) {
+                        output.push(source_output.clone());
+                    }
                 }
             }
 
             // Merge it so that we only have one transition per symbol
-            let mut dfa_transitions = DfaTransitions { state_id: states.len() as StateId, transitions: transitions, output: output };
+            let mut dfa_transitions = DfaTransitions { transitions: transitions, output: output };
             dfa_transitions.merge_states();
 
-            // Process any generated states that are not already in the DFA
+            // Assign IDs to any generated states that are not already in the DFA and queue them for processing (as states
+            // are processed in the order they're discovered, each ID will match the index of the state in the states list)
             for &(_, ref maybe_new_state) in &dfa_transitions.transitions {
                 if !known_states.contains_key(maybe_new_state) {
-                    to_process.push(maybe_new_state.clone());
+                    let new_state_id = known_states.len() as StateId;
+
+                    known_states.insert(maybe_new_state.clone(), new_state_id);
+                    to_process.push_back(maybe_new_state.clone());
                 }
             }
 
             // Store the new state
-            known_states.insert(state.clone(), dfa_transitions.state_id);
             states.push(dfa_transitions);
         }
 
         // Build the DFA
         let mut builder = self.builder;
 
-        for mut dfa_state in states {
+        for dfa_state in states {
             builder.start_state();
 
-            if let Some(output_symbol) = dfa_state.output_symbol() {
-                builder.accept(output_symbol.clone());
+            if let Some(output_symbol) = dfa_state.output_symbol(&resolver) {
+                builder.accept(output_symbol);
             }
 
             for (symbol, target_state) in dfa_state.transitions {
@@ -240,6 +286,75 @@ impl<InputSymbol: Ord+Clone, OutputSymbol: Ord+Clone, DfaType, Ndfa: StateMachin
     }
 }
 
+///
+/// The default resolver for states with more than one output symbol, which picks the symbol that is ordered lowest
+///
+fn lowest_output<OutputSymbol: Ord+Clone>(outputs: &[OutputSymbol]) -> OutputSymbol {
+    outputs.iter().min().unwrap().clone()
+}
+
+///
+/// Scratch space that can be reused when compiling many NDFAs into DFAs
+///
+/// Compiling an NDFA needs a symbol map to remove overlapping ranges and some collections to keep track of the DFA states
+/// that have been found. When compiling a lot of small patterns, allocating these every time adds up: a context keeps
+/// them around, clearing them instead of reallocating them between compilations.
+///
+pub struct CompilerContext<Symbol: Ord+Clone+Countable> {
+    /// Symbol map used to find the non-overlapping ranges for an NDFA
+    symbol_map: SymbolMap<Symbol>,
+
+    /// The DFA states that have been found, and their IDs
+    known_states: HashMap<DfaState, StateId>,
+
+    /// DFA states that are waiting to be processed
+    to_process: VecDeque<DfaState>
+}
+
+impl<Symbol: Ord+Clone+Countable> CompilerContext<Symbol> {
+    ///
+    /// Creates a new compiler context
+    ///
+    pub fn new() -> CompilerContext<Symbol> {
+        CompilerContext { symbol_map: SymbolMap::new(), known_states: HashMap::new(), to_process: VecDeque::new() }
+    }
+
+    ///
+    /// Compiles an NDFA into a DFA using the scratch space in this context
+    ///
+    /// The NDFA doesn't need to have had `fix_overlapping_ranges()` called on it. As with `DfaCompiler::compile()`, the
+    /// lowest output symbol is used for states with more than one.
+    ///
+    pub fn compile<OutputSymbol: Ord+Clone>(&mut self, ndfa: Ndfa<SymbolRange<Symbol>, OutputSymbol>) -> SymbolRangeDfa<Symbol, OutputSymbol> {
+        self.compile_with_resolver(ndfa, lowest_output)
+    }
+
+    ///
+    /// Compiles an NDFA into a DFA using the scratch space in this context, using a function to decide on the output symbol
+    /// for states with more than one
+    ///
+    /// The resolver is called in the same way as for `DfaCompiler::compile_with_resolver()`.
+    ///
+    pub fn compile_with_resolver<OutputSymbol: Ord+Clone, Resolver: Fn(&[OutputSymbol]) -> OutputSymbol>(&mut self, mut ndfa: Ndfa<SymbolRange<Symbol>, OutputSymbol>, resolver: Resolver) -> SymbolRangeDfa<Symbol, OutputSymbol> {
+        // Remove overlapping ranges using our symbol map
+        self.symbol_map.clear();
+        ndfa.fix_overlapping_ranges_using(&mut self.symbol_map);
+
+        // Compile using our state collections
+        self.known_states.clear();
+        self.to_process.clear();
+
+        let compiler = DfaCompiler::new(ndfa, SymbolRangeDfaBuilder::new());
+        compiler.compile_using(resolver, &mut self.known_states, &mut self.to_process)
+    }
+}
+
+impl<Symbol: Ord+Clone+Countable> Default for CompilerContext<Symbol> {
+    fn default() -> CompilerContext<Symbol> {
+        CompilerContext::new()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -248,6 +363,9 @@ mod test {
     use super::super::symbol_range_dfa::*;
     use super::super::pattern_matcher::*;
     use super::super::symbol_reader::*;
+    use super::super::ndfa::*;
+    use super::super::matches::*;
+    use super::super::prepare::*;
 
     #[test]
     fn can_create_compiler() {
@@ -343,4 +461,225 @@ mod test {
             assert!(false);
         }
     }
+
+    #[test]
+    fn default_compiler_picks_lowest_output() {
+        let mut ndfa = Ndfa::new();
+        exactly("abc").compile(&mut ndfa, 0);
+        let end_state = exactly("abc").compile(&mut ndfa, 0);
+        ndfa.set_output_symbol(end_state, 2);
+        ndfa.set_output_symbol(3, 1);
+        ndfa.fix_overlapping_ranges();
+
+        let state_machine = DfaCompiler::build(ndfa, SymbolRangeDfaBuilder::new());
+
+        assert!(match_pattern(state_machine.start(), &mut "abc".read_symbols()).is_accepted(&1));
+    }
+
+    #[test]
+    fn resolver_can_pick_highest_output() {
+        let mut ndfa = Ndfa::new();
+        exactly("abc").compile(&mut ndfa, 0);
+        let end_state = exactly("abc").compile(&mut ndfa, 0);
+        ndfa.set_output_symbol(end_state, 2);
+        ndfa.set_output_symbol(3, 1);
+        ndfa.fix_overlapping_ranges();
+
+        let state_machine = DfaCompiler::build_with_resolver(ndfa, SymbolRangeDfaBuilder::new(), |outputs: &[u32]| *outputs.iter().max().unwrap());
+
+        assert!(match_pattern(state_machine.start(), &mut "abc".read_symbols()).is_accepted(&2));
+        assert!(state_machine.accepting_states().values().all(|output| *output == 2));
+    }
+
+    #[test]
+    fn resolver_can_pick_first_added_output() {
+        let mut ndfa = Ndfa::new();
+        let first_end   = exactly("abc").compile(&mut ndfa, 0);
+        let second_end  = MatchRange('a', 'z').repeat_forever(1).compile(&mut ndfa, 0);
+        let third_end   = exactly("ab").append(MatchRange('a', 'z')).compile(&mut ndfa, 0);
+        ndfa.set_output_symbol(first_end, 3);
+        ndfa.set_output_symbol(second_end, 1);
+        ndfa.set_output_symbol(third_end, 2);
+        ndfa.fix_overlapping_ranges();
+
+        let state_machine = DfaCompiler::build_with_resolver(ndfa, SymbolRangeDfaBuilder::new(), |outputs: &[u32]| outputs[0]);
+
+        assert!(match_pattern(state_machine.start(), &mut "abc".read_symbols()).is_accepted(&3));
+        assert!(match_pattern(state_machine.start(), &mut "abd".read_symbols()).is_accepted(&1));
+        assert!(match_pattern(state_machine.start(), &mut "xyz".read_symbols()).is_accepted(&1));
+    }
+
+    #[test]
+    fn resolver_is_passed_outputs_in_ndfa_state_order() {
+        let mut ndfa = Ndfa::new();
+        let first_end   = exactly("abc").compile(&mut ndfa, 0);
+        let second_end  = MatchRange('a', 'z').repeat_forever(1).compile(&mut ndfa, 0);
+        let third_end   = exactly("ab").append(MatchRange('a', 'z')).compile(&mut ndfa, 0);
+        ndfa.set_output_symbol(first_end, 3);
+        ndfa.set_output_symbol(second_end, 1);
+        ndfa.set_output_symbol(third_end, 2);
+        ndfa.fix_overlapping_ranges();
+
+        DfaCompiler::build_with_resolver(ndfa, SymbolRangeDfaBuilder::new(), |outputs: &[u32]| {
+            assert!(outputs == &[3, 1, 2] || outputs == &[1, 2]);
+            outputs[0]
+        });
+    }
+
+    #[test]
+    fn resolver_is_not_called_for_single_output() {
+        let ndfa = exactly("abc").or("abd").to_ndfa(1);
+
+        let state_machine = DfaCompiler::build_with_resolver(ndfa, SymbolRangeDfaBuilder::new(), |_: &[u32]| -> u32 { panic!("Resolver should not be called") });
+
+        assert!(match_pattern(state_machine.start(), &mut "abd".read_symbols()).is_accepted(&1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn cannot_compile_state_machine_with_default_transitions() {
+        let mut builder = SymbolRangeDfaBuilder::new();
+
+        builder.start_state();
+        builder.transition(SymbolRange::new('a', 'a'), 1);
+        builder.default_transition(1);
+        builder.start_state();
+        builder.accept(());
+
+        let dfa: SymbolRangeDfa<char, ()> = builder.build();
+        let _: SymbolRangeDfa<char, ()> = DfaCompiler::build(dfa, SymbolRangeDfaBuilder::new());
+    }
+
+    #[test]
+    fn compiling_same_ndfa_twice_produces_identical_dfas() {
+        let pattern = exactly("abc").or("abd").or(MatchRange('0', '9').repeat_forever(1)).append(MatchRange('x', 'z').repeat_forever(0));
+
+        let first: SymbolRangeDfa<char, u32>    = DfaCompiler::build(pattern.to_ndfa(1), SymbolRangeDfaBuilder::new());
+        let second: SymbolRangeDfa<char, u32>   = DfaCompiler::build(pattern.to_ndfa(1), SymbolRangeDfaBuilder::new());
+
+        assert!(first == second);
+        assert!(first.describe() == second.describe());
+    }
+
+    #[test]
+    fn states_are_numbered_in_breadth_first_order() {
+        let ndfa                                = exactly("ab").or("c").to_ndfa(1);
+        let dfa: SymbolRangeDfa<char, u32>      = DfaCompiler::build(ndfa, SymbolRangeDfaBuilder::new());
+
+        assert!(dfa.get_transitions_for_state(0) == vec![(SymbolRange::new('a', 'a'), 1), (SymbolRange::new('c', 'c'), 2)]);
+        assert!(dfa.get_transitions_for_state(1) == vec![(SymbolRange::new('b', 'b'), 3)]);
+        assert!(dfa.count_states() == 4);
+    }
+
+    #[test]
+    fn context_compiles_same_dfas_as_standalone() {
+        let mut context = CompilerContext::new();
+
+        for index in 0..1000 {
+            let keyword         = format!("kw{}", index);
+            let pattern         = exactly(keyword.as_str()).or(MatchRange('0', '9').repeat_forever(1));
+            let mut ndfa        = Ndfa::new();
+            let end_state       = pattern.compile(&mut ndfa, 0);
+            ndfa.set_output_symbol(end_state, ());
+
+            let with_context    = ndfa.clone().compile_with_context(&mut context);
+            let standalone      = pattern.prepare_to_match();
+
+            assert!(with_context.count_states() == standalone.count_states());
+            assert!(matches_prepared(keyword.as_str(), &with_context) == Some(keyword.len()));
+            assert!(matches_prepared("123", &with_context) == Some(3));
+            assert!(matches_prepared("kw", &with_context) == None);
+        }
+    }
+
+    #[test]
+    fn context_reuses_allocated_space() {
+        let mut context = CompilerContext::new();
+        let compile     = |context: &mut CompilerContext<char>, keyword: &str| {
+            let mut ndfa    = Ndfa::new();
+            let end_state   = exactly(keyword).compile(&mut ndfa, 0);
+            ndfa.set_output_symbol(end_state, ());
+
+            ndfa.compile_with_context(context)
+        };
+
+        // Compile the largest pattern first so the context has enough space for all of the others
+        compile(&mut context, "kw999");
+
+        let known_states_capacity   = context.known_states.capacity();
+        let to_process_capacity     = context.to_process.capacity();
+        let symbol_map_ranges       = context.symbol_map.ranges().len();
+
+        for index in 0..1000 {
+            let keyword = format!("kw{}", index);
+            let dfa     = compile(&mut context, &keyword);
+
+            assert!(matches_prepared(keyword.as_str(), &dfa) == Some(keyword.len()));
+            assert!(context.known_states.capacity() == known_states_capacity);
+            assert!(context.to_process.capacity() == to_process_capacity);
+        }
+
+        assert!(symbol_map_ranges > 0);
+    }
+
+    #[test]
+    fn context_allocates_less_than_standalone_compiler() {
+        let ndfa_for_keyword = |keyword: &str| {
+            let mut ndfa    = Ndfa::new();
+            let end_state   = exactly(keyword).compile(&mut ndfa, 0);
+            ndfa.set_output_symbol(end_state, ());
+
+            ndfa
+        };
+
+        // The standalone compiler starts with new collections for every NDFA, so has to allocate space for each one
+        let mut standalone_allocations = 0;
+
+        for index in 0..1000 {
+            let mut ndfa            = ndfa_for_keyword(&format!("kw{}", index));
+            let mut known_states    = HashMap::new();
+            let mut to_process      = VecDeque::new();
+
+            ndfa.fix_overlapping_ranges();
+            DfaCompiler::new(ndfa, SymbolRangeDfaBuilder::new()).compile_using(lowest_output, &mut known_states, &mut to_process);
+
+            if known_states.capacity() > 0 || to_process.capacity() > 0 {
+                standalone_allocations += 1;
+            }
+        }
+
+        // The context only needs to allocate when an NDFA needs more space than any of the ones before it
+        let mut context             = CompilerContext::new();
+        let mut context_allocations = 0;
+
+        for index in 0..1000 {
+            let capacities = (context.known_states.capacity(), context.to_process.capacity());
+            ndfa_for_keyword(&format!("kw{}", index)).compile_with_context(&mut context);
+
+            if (context.known_states.capacity(), context.to_process.capacity()) != capacities {
+                context_allocations += 1;
+            }
+        }
+
+        assert!(standalone_allocations == 1000);
+        assert!(context_allocations < 10);
+    }
+
+    #[test]
+    fn context_can_use_resolver() {
+        let mut context = CompilerContext::new();
+        let mut ndfa    = Ndfa::new();
+
+        let first_end   = exactly("abc").compile(&mut ndfa, 0);
+        let second_end  = MatchRange('a', 'z').repeat_forever(1).compile(&mut ndfa, 0);
+        ndfa.set_output_symbol(first_end, 2);
+        ndfa.set_output_symbol(second_end, 1);
+
+        let lowest  = context.compile(ndfa.clone());
+        let highest = context.compile_with_resolver(ndfa, |outputs: &[u32]| *outputs.iter().max().unwrap());
+
+        assert!(match_pattern(lowest.start(), &mut "abc".read_symbols()).is_accepted(&1));
+        assert!(match_pattern(highest.start(), &mut "abc".read_symbols()).is_accepted(&2));
+        assert!(match_pattern(highest.start(), &mut "abd".read_symbols()).is_accepted(&1));
+    }
 }
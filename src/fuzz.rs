@@ -0,0 +1,306 @@
+//
+//   Copyright 2016 Andrew Hunter
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+//
+
+//!
+//! Helpers for property testing patterns, available when the `rand` feature is enabled.
+//!
+//! `random_pattern()` generates arbitrary patterns, and `random_match()` and `random_non_match()` generate strings that a
+//! pattern does or does not accept. These can be combined to check that some property holds for many different inputs:
+//!
+//! ```
+//! # extern crate rand;
+//! # extern crate concordance;
+//! # use concordance::*;
+//! # use rand::SeedableRng;
+//! # fn main() {
+//! let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+//! let pattern = exactly("a").or(MatchRange('0', '9').repeat_forever(1));
+//!
+//! if let Some(input) = random_match(&pattern, &mut rng) {
+//!     assert!(matches(input.as_str(), pattern) == Some(input.chars().count()));
+//! }
+//! # }
+//! ```
+//!
+
+use rand::Rng;
+
+use super::countable::*;
+use super::symbol_range::*;
+use super::state_machine::*;
+use super::regular_pattern::*;
+use super::prepare::*;
+use super::matches::*;
+use super::symbol_range_dfa::*;
+
+///
+/// The characters used in the patterns generated by `random_pattern()`
+///
+/// This is kept small so that the generated patterns frequently overlap with each other.
+///
+const PATTERN_ALPHABET: &[char] = &['a', 'b', 'c', 'd', 'e'];
+
+///
+/// The number of symbols `random_match()` will generate before it starts heading directly for an accepting state
+///
+const MATCH_LENGTH_BUDGET: usize = 16;
+
+///
+/// The number of strings `random_non_match()` will try before giving up
+///
+const NON_MATCH_ATTEMPTS: usize = 256;
+
+///
+/// Generates a random pattern over a small alphabet of characters
+///
+/// `depth` limits how deeply the generated patterns can be nested.
+///
+pub fn random_pattern<R: Rng>(rng: &mut R, depth: usize) -> Pattern<char> {
+    let choice = if depth == 0 { rng.gen_range(0..3) } else { rng.gen_range(0..7) };
+
+    match choice {
+        0 => {
+            let length = rng.gen_range(1..4);
+            Match((0..length).map(|_| random_alphabet_char(rng)).collect())
+        },
+
+        1 => {
+            let first   = random_alphabet_char(rng);
+            let second  = random_alphabet_char(rng);
+            if first <= second { MatchRange(first, second) } else { MatchRange(second, first) }
+        },
+
+        2 => Epsilon,
+
+        3 => {
+            let count = rng.gen_range(2..4);
+            MatchAll((0..count).map(|_| random_pattern(rng, depth-1)).collect())
+        },
+
+        4 => {
+            let count = rng.gen_range(2..4);
+            MatchAny((0..count).map(|_| random_pattern(rng, depth-1)).collect())
+        },
+
+        5 => {
+            let min = rng.gen_range(0..3);
+            RepeatInfinite(min, Box::new(random_pattern(rng, depth-1)))
+        },
+
+        _ => {
+            let min = rng.gen_range(0..3);
+            let max = rng.gen_range(min+1..min+4);
+            Repeat(min..max, Box::new(random_pattern(rng, depth-1)))
+        }
+    }
+}
+
+///
+/// Generates a random string that is accepted by a pattern, or `None` if the pattern doesn't accept anything
+///
+/// The string is generated by taking a random walk through the NDFA for the pattern, only following transitions that can
+/// still lead to an accepting state.
+///
+pub fn random_match<R: Rng>(pattern: &Pattern<char>, rng: &mut R) -> Option<String> {
+    let ndfa        = pattern.to_ndfa(());
+    let distances   = distance_to_accept(&*ndfa);
+
+    // Patterns with no reachable accepting state can't generate a match
+    if distances[0].is_none() {
+        return None;
+    }
+
+    let mut result  = String::new();
+    let mut length  = 0;
+    let mut state   = 0;
+
+    loop {
+        let accepting   = ndfa.output_symbol_for_state(state).is_some();
+        let mut live: Vec<_> = ndfa.get_transitions_for_state(state).into_iter()
+            .filter(|&(_, target)| distances[target as usize].is_some())
+            .collect();
+
+        if accepting && (live.is_empty() || length >= MATCH_LENGTH_BUDGET || rng.gen_range(0..4) == 0) {
+            return Some(result);
+        }
+
+        // Once the budget has run out, only move closer to an accepting state so that the walk always finishes
+        if length >= MATCH_LENGTH_BUDGET {
+            let current_distance = distances[state as usize];
+            live.retain(|&(_, target)| distances[target as usize] < current_distance);
+        }
+
+        let (range, target) = live.swap_remove(rng.gen_range(0..live.len()));
+
+        result.push(random_char_in_range(&range, rng));
+        length  += 1;
+        state   = target;
+    }
+}
+
+///
+/// Generates a random string that is not accepted by a pattern, or `None` if one could not be found
+///
+/// The strings that are tried are made up of the characters at and around the edges of the ranges used by the pattern.
+///
+pub fn random_non_match<R: Rng>(pattern: &Pattern<char>, rng: &mut R) -> Option<String> {
+    let ndfa            = pattern.to_ndfa(());
+
+    // Build an alphabet from the edges of every range in the NDFA
+    let mut alphabet    = vec!['\u{0000}'];
+
+    for state in 0..ndfa.count_states() {
+        for (range, _) in ndfa.get_transitions_for_state(state) {
+            alphabet.push(range.lowest);
+            alphabet.push(range.highest);
+            alphabet.push(range.highest.next());
+        }
+    }
+
+    alphabet.sort();
+    alphabet.dedup();
+
+    let dfa: SymbolRangeDfa<char, ()> = ndfa.prepare_to_match();
+
+    // Try random strings until one is not accepted
+    for _ in 0..NON_MATCH_ATTEMPTS {
+        let length      = rng.gen_range(0..MATCH_LENGTH_BUDGET/2);
+        let candidate   = (0..length).map(|_| alphabet[rng.gen_range(0..alphabet.len())]).collect::<String>();
+
+        if matches_prepared(candidate.as_str(), &dfa) != Some(length) {
+            return Some(candidate);
+        }
+    }
+
+    None
+}
+
+///
+/// Picks a random character from the pattern alphabet
+///
+fn random_alphabet_char<R: Rng>(rng: &mut R) -> char {
+    PATTERN_ALPHABET[rng.gen_range(0..PATTERN_ALPHABET.len())]
+}
+
+///
+/// Picks a random character from a range
+///
+fn random_char_in_range<R: Rng>(range: &SymbolRange<char>, rng: &mut R) -> char {
+    let code = rng.gen_range((range.lowest as u32)..=(range.highest as u32));
+
+    // Surrogates aren't valid characters, so use the start of the range if one is picked
+    ::std::char::from_u32(code).unwrap_or(range.lowest)
+}
+
+///
+/// Finds the number of transitions needed to get from each state in a state machine to an accepting state
+///
+/// States that can't reach an accepting state have a distance of `None`.
+///
+fn distance_to_accept(state_machine: &StateMachine<SymbolRange<char>, ()>) -> Vec<Option<usize>> {
+    let num_states      = state_machine.count_states() as usize;
+    let mut distances   = vec![None; num_states];
+    let mut sources     = vec![vec![]; num_states];
+    let mut to_visit    = vec![];
+
+    for state in 0..num_states {
+        for (_, target) in state_machine.get_transitions_for_state(state as StateId) {
+            sources[target as usize].push(state);
+        }
+
+        if state_machine.output_symbol_for_state(state as StateId).is_some() {
+            distances[state] = Some(0);
+            to_visit.push(state);
+        }
+    }
+
+    // Breadth-first search backwards from the accepting states
+    let mut distance = 0;
+
+    while !to_visit.is_empty() {
+        distance += 1;
+
+        let mut next_visit = vec![];
+
+        for state in to_visit {
+            for &source in &sources[state] {
+                if distances[source].is_none() {
+                    distances[source] = Some(distance);
+                    next_visit.push(source);
+                }
+            }
+        }
+
+        to_visit = next_visit;
+    }
+
+    distances
+}
+
+#[cfg(test)]
+mod test {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    #[test]
+    fn random_match_is_accepted_by_pattern() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let pattern = exactly("abc").or(MatchRange('0', '9').repeat_forever(1));
+
+        for _ in 0..100 {
+            let input = random_match(&pattern, &mut rng).unwrap();
+
+            assert!(matches(input.as_str(), pattern.clone()) == Some(input.chars().count()));
+        }
+    }
+
+    #[test]
+    fn empty_language_has_no_random_match() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        assert!(random_match(&MatchAny(vec![]), &mut rng).is_none());
+    }
+
+    #[test]
+    fn random_non_match_is_rejected_by_pattern() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let pattern = exactly("abc");
+
+        let input = random_non_match(&pattern, &mut rng).unwrap();
+
+        assert!(input != "abc");
+        assert!(matches(input.as_str(), pattern) != Some(input.chars().count()));
+    }
+
+    #[test]
+    fn random_patterns_accept_their_random_matches() {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        for _ in 0..200 {
+            let pattern = random_pattern(&mut rng, 3);
+
+            if let Some(input) = random_match(&pattern, &mut rng) {
+                assert!(matches(input.as_str(), pattern.clone()) == Some(input.chars().count()));
+            }
+
+            if let Some(input) = random_non_match(&pattern, &mut rng) {
+                assert!(matches(input.as_str(), pattern.clone()) != Some(input.chars().count()));
+            }
+        }
+    }
+}
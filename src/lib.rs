@@ -20,6 +20,8 @@
 
 #[macro_use] extern crate serde;
 #[macro_use] extern crate serde_derive;
+#[cfg(feature = "unicode-segmentation")] extern crate unicode_segmentation;
+#[cfg(feature = "rand")] extern crate rand;
 
 pub use self::countable::*;
 pub use self::symbol_range::*;
@@ -38,6 +40,11 @@ pub use self::tape::*;
 pub use self::split_reader::*;
 pub use self::tokenizer::*;
 pub use self::tagged_stream::*;
+pub use self::ord_symbol::*;
+pub use self::regex_parse::*;
+pub use self::bounded_range::*;
+pub use self::pattern_cache::*;
+#[cfg(feature = "rand")] pub use self::fuzz::*;
 
 pub mod countable;
 pub mod symbol_range;
@@ -57,3 +64,8 @@ pub mod tape;
 pub mod split_reader;
 pub mod tokenizer;
 pub mod tagged_stream;
+pub mod ord_symbol;
+pub mod regex_parse;
+pub mod bounded_range;
+pub mod pattern_cache;
+#[cfg(feature = "rand")] pub mod fuzz;
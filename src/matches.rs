@@ -40,10 +40,15 @@
 //! ```
 //!
 
+use std::rc::Rc;
+
 use super::symbol_range_dfa::*;
 use super::symbol_reader::*;
 use super::pattern_matcher::*;
 use super::prepare::*;
+use super::countable::*;
+use super::tokenizer::*;
+use super::regular_pattern::*;
 
 ///
 /// Runs a DFA against a symbol stream and returns its final state
@@ -82,6 +87,43 @@ where State: MatchingState<'a, InputSymbol, OutputSymbol> {
     current_state
 }
 
+///
+/// Runs a DFA against a symbol stream, stopping at the first accepting state
+///
+/// This is the same as `match_pattern` except that it returns the shortest match rather than the longest. It will not
+/// read any further symbols from the stream once a match has been found. The matching state has to be able to report
+/// when it is accepting, so this needs a `PeekableMatchingState`.
+///
+/// ```
+/// # use concordance::*;
+/// let matcher      = exactly("a").repeat_forever(1).prepare_to_match();
+/// let match_result = match_pattern_shortest(matcher.start(), &mut "aaa".read_symbols()); // == Accept(1, &())
+/// # assert!(match match_result { Accept(count, _) => count == 1, _ => false });
+/// ```
+///
+pub fn match_pattern_shortest<'a, InputSymbol: Ord, OutputSymbol, State>(start_state: MatchAction<'a, OutputSymbol, State>, symbol_reader: &mut SymbolReader<InputSymbol>) -> MatchAction<'a, OutputSymbol, State>
+where State: PeekableMatchingState<'a, InputSymbol, OutputSymbol> {
+    let mut current_state = start_state;
+
+    while let More(this_state) = current_state {
+        // Stop as soon as we reach an accepting state
+        if let Some((length, output)) = this_state.peek_accept() {
+            return Accept(length, output);
+        }
+
+        let next_state = 
+            if let Some(next_char) = symbol_reader.next_symbol() {
+                this_state.next(next_char)
+            } else {
+                this_state.finish()
+            };
+
+        current_state = next_state;
+    }
+
+    current_state
+}
+
 ///
 /// Runs a pattern matcher against a stream, and returns the number of characters matching if it accepted the stream
 ///
@@ -156,6 +198,106 @@ where   Reader: SymbolReader<Symbol>+'a
     matches_symbol_range(&matcher, &mut reader)
 }
 
+///
+/// Matches a source stream against a prepared pattern that's shared via a reference-counted pointer
+///
+/// This is the same as `matches_prepared`, but is convenient when a single prepared pattern is shared between several
+/// owners (for example, a set of closures).
+///
+/// ```
+/// # use concordance::*;
+/// # use std::rc::Rc;
+/// let prepared = Rc::new(exactly("abc").repeat_forever(1).prepare_to_match());
+///
+/// matches_rc("abcabc", prepared.clone());     // == Some(6)
+/// # assert!(matches_rc("abcabc", prepared.clone()) == Some(6));
+/// ```
+///
+pub fn matches_rc<'a, Symbol, OutputSymbol, Reader, Source>(source: Source, matcher: Rc<SymbolRangeDfa<Symbol, OutputSymbol>>) -> Option<usize>
+where   Reader: SymbolReader<Symbol>+'a
+,       Source: SymbolSource<'a, Symbol, SymbolReader=Reader>
+,       Symbol: Ord
+,       OutputSymbol: 'static {
+    let mut reader = source.read_symbols();
+
+    matches_symbol_range(&*matcher, &mut reader)
+}
+
+///
+/// Matches a symbol reader against a pattern
+///
+/// This is the same as `matches` except that it reads directly from a reader rather than a source, so it can be used
+/// with readers that have already been created (for example, a `Tape` or a reader adapted using `map_symbols`). Symbols
+/// are consumed from the reader while matching, and the matcher may read past the end of the match.
+///
+/// ```
+/// # use concordance::*;
+/// let mut reader = "abcabc".read_symbols().map_symbols(|c| c.to_ascii_uppercase());
+///
+/// matches_reader(&mut reader, exactly("ABC").repeat_forever(1));  // == Some(6)
+/// # let mut reader = "abcabc".read_symbols().map_symbols(|c| c.to_ascii_uppercase());
+/// # assert!(matches_reader(&mut reader, exactly("ABC").repeat_forever(1)) == Some(6));
+/// ```
+///
+pub fn matches_reader<Symbol, OutputSymbol, Prepare>(reader: &mut SymbolReader<Symbol>, pattern: Prepare) -> Option<usize>
+where   Prepare: PrepareToMatch<SymbolRangeDfa<Symbol, OutputSymbol>>
+,       Symbol: Ord
+,       OutputSymbol: 'static {
+    let matcher = pattern.prepare_to_match();
+
+    matches_symbol_range(&matcher, reader)
+}
+
+///
+/// Counts the number of non-overlapping times a pattern occurs in a source stream
+///
+/// The stream is scanned in the same way as a `Tokenizer` used as an iterator: at each position the longest match is found,
+/// and scanning continues after it. If there's no match (or only a zero-length match) at a position, it's skipped and
+/// matching is tried again at the next symbol.
+///
+/// ```
+/// # use concordance::*;
+/// count_matches("ababab", "ab");                                  // == 3
+/// count_matches("a1b22c", MatchRange('0', '9').repeat_forever(1)); // == 2
+/// # assert!(count_matches("ababab", "ab") == 3);
+/// # assert!(count_matches("a1b22c", MatchRange('0', '9').repeat_forever(1)) == 2);
+/// ```
+///
+pub fn count_matches<'a, Symbol, OutputSymbol, Prepare, Reader, Source>(source: Source, pattern: Prepare) -> usize
+where   Prepare: PrepareToMatch<SymbolRangeDfa<Symbol, OutputSymbol>>
+,       Reader: SymbolReader<Symbol>+'a
+,       Source: SymbolSource<'a, Symbol, SymbolReader=Reader>
+,       Symbol: Ord+Clone+Countable
+,       OutputSymbol: Ord+Clone+'static {
+    let tokenizer = Tokenizer::new(source.read_symbols(), pattern);
+
+    tokenizer.count()
+}
+
+///
+/// Matches the end of a source stream against a pattern, returning the length of the longest suffix that matches
+///
+/// This reads the whole of the source stream, then matches the reversed pattern against it backwards.
+///
+/// ```
+/// # use concordance::*;
+/// matches_suffix("hello", "llo");                                         // == Some(3)
+/// matches_suffix("abc123", MatchRange('0', '9').repeat_forever(1));       // == Some(3)
+/// # assert!(matches_suffix("hello", "llo") == Some(3));
+/// # assert!(matches_suffix("abc123", MatchRange('0', '9').repeat_forever(1)) == Some(3));
+/// ```
+///
+pub fn matches_suffix<'a, Symbol, PatternType, Reader, Source>(source: Source, pattern: PatternType) -> Option<usize>
+where   PatternType: IntoPattern<Symbol>
+,       Reader: SymbolReader<Symbol>+'a
+,       Source: SymbolSource<'a, Symbol, SymbolReader=Reader>
+,       Symbol: Ord+Clone+Countable+'static {
+    let mut symbols = source.read_symbols().to_vec();
+    symbols.reverse();
+
+    matches(&symbols, pattern.into_pattern().reverse())
+}
+
 #[cfg(test)]
 mod test {
     use super::super::*;
@@ -165,6 +307,21 @@ mod test {
         assert!(matches("abcabc", exactly("abc").repeat_forever(1)).is_some());
     }
 
+    #[test]
+    fn match_owned_string() {
+        let source = String::from("abcabc");
+
+        assert!(matches(source, exactly("abc").repeat_forever(1)) == Some(6));
+    }
+
+    #[test]
+    fn match_string_reference() {
+        let source = String::from("abcabc");
+
+        assert!(matches(&source, exactly("abc").repeat_forever(1)) == Some(6));
+        assert!(matches(&source, "abd").is_none());
+    }
+
     #[test]
     fn match_prepared() {
         let prepared = exactly("abc").repeat_forever(1).prepare_to_match();
@@ -174,6 +331,118 @@ mod test {
         assert!(matches_prepared("abcabcabc", &prepared) == Some(9));
     }
 
+    #[test]
+    fn match_prepared_by_reference() {
+        let prepared = exactly("abc").repeat_forever(1).prepare_to_match();
+
+        assert!(matches("abcabc", &prepared) == Some(6));
+        assert!(matches("abd", &prepared) == None);
+    }
+
+    #[test]
+    fn match_shared_prepared_pattern() {
+        use std::rc::Rc;
+
+        let prepared    = Rc::new(exactly("abc").repeat_forever(1).prepare_to_match());
+        let match_a     = { let prepared = prepared.clone(); move |input| matches_rc(input, prepared.clone()) };
+        let match_b     = { let prepared = prepared.clone(); move |input| matches_rc(input, prepared.clone()) };
+
+        assert!(match_a("abcabc") == Some(6));
+        assert!(match_b("abc") == Some(3));
+        assert!(match_a("def") == None);
+        assert!(matches_rc("abcabcabc", prepared) == Some(9));
+    }
+
+    #[test]
+    fn count_digit_runs() {
+        assert!(count_matches("a1b22c", MatchRange('0', '9').repeat_forever(1)) == 2);
+    }
+
+    #[test]
+    fn count_repeated_literal() {
+        assert!(count_matches("ababab", "ab") == 3);
+        assert!(count_matches("aabab", "ab") == 2);
+        assert!(count_matches("", "ab") == 0);
+    }
+
+    #[test]
+    fn count_ignores_zero_length_matches() {
+        assert!(count_matches("xyz", exactly("a").repeat_forever(0)) == 0);
+        assert!(count_matches("xaaz", exactly("a").repeat_forever(0)) == 1);
+    }
+
+    #[test]
+    fn match_prepared_pattern_list() {
+        let prepared = (&[exactly("ab"), exactly("cd")][..]).prepare_to_match();
+
+        assert!(matches_prepared("ab", &prepared) == Some(2));
+        assert!(matches_prepared("cd", &prepared) == Some(2));
+        assert!(matches_prepared("abcd", &prepared) == Some(2));
+        assert!(matches_prepared("ac", &prepared) == None);
+    }
+
+    #[test]
+    fn match_prepared_pattern_vec() {
+        let patterns = vec![exactly("ab"), exactly("cd")];
+        let prepared = (&patterns).prepare_to_match();
+
+        assert!(matches_prepared("ab", &prepared) == Some(2));
+        assert!(matches_prepared("cd", &prepared) == Some(2));
+        assert!(matches("cd", &patterns) == Some(2));
+    }
+
+    #[test]
+    fn match_reader_from_tape() {
+        let mut tape = Tape::new("abcabcdef".read_symbols());
+
+        assert!(matches_reader(&mut tape, exactly("abc").repeat_forever(1)) == Some(6));
+    }
+
+    #[test]
+    fn match_reader_from_mapped_reader() {
+        let source      = vec![1, 2, 3, 1, 2, 3];
+        let mut reader  = source.read_symbols().map_symbols(|x| x*10);
+
+        assert!(matches_reader(&mut reader, exactly(&vec![10, 20, 30]).repeat_forever(1)) == Some(6));
+    }
+
+    #[test]
+    fn match_reader_can_reject() {
+        let mut reader = "abd".read_symbols();
+
+        assert!(matches_reader(&mut reader, "abc") == None);
+    }
+
+    #[test]
+    fn shortest_match_stops_at_first_accept() {
+        let matcher = exactly("a").repeat_forever(1).prepare_to_match();
+
+        let shortest    = match_pattern_shortest(matcher.start(), &mut "aaa".read_symbols());
+        let greedy      = match_pattern(matcher.start(), &mut "aaa".read_symbols());
+
+        assert!(match shortest { Accept(1, _) => true, _ => false });
+        assert!(match greedy { Accept(3, _) => true, _ => false });
+    }
+
+    #[test]
+    fn shortest_match_does_not_read_past_match() {
+        let matcher     = exactly("ab").prepare_to_match();
+        let mut reader  = "abc".read_symbols();
+
+        let shortest    = match_pattern_shortest(matcher.start(), &mut reader);
+
+        assert!(match shortest { Accept(2, _) => true, _ => false });
+        assert!(reader.next_symbol() == Some('c'));
+    }
+
+    #[test]
+    fn shortest_match_can_reject() {
+        let matcher     = exactly("ab").prepare_to_match();
+        let shortest    = match_pattern_shortest(matcher.start(), &mut "ac".read_symbols());
+
+        assert!(match shortest { Reject => true, _ => false });
+    }
+
     #[test]
     fn match_single_repeat() {
         assert!(matches("abc", exactly("abc").repeat_forever(1)).is_some());
@@ -225,4 +494,17 @@ mod test {
     fn match_zero_repeats() {
         assert!(matches("", exactly("abc").repeat_forever(0)).is_some());
     }
+
+    #[test]
+    fn match_suffix() {
+        assert!(matches_suffix("hello", "llo") == Some(3));
+        assert!(matches_suffix("hello", "xyz") == None);
+        assert!(matches_suffix("hello", "hel") == None);
+    }
+
+    #[test]
+    fn match_longest_suffix() {
+        assert!(matches_suffix("abababab", exactly("ab").repeat_forever(1)) == Some(8));
+        assert!(matches_suffix("xxabab", exactly("ab").repeat_forever(1)) == Some(4));
+    }
 }
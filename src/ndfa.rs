@@ -41,6 +41,15 @@ use super::state_machine::*;
 use super::overlapping_symbols::*;
 use super::countable::*;
 use super::symbol_range::*;
+use super::ord_symbol::*;
+use super::bounded_range::*;
+use super::dfa_compiler::*;
+use super::symbol_range_dfa::*;
+
+///
+/// The largest number of states that `Ndfa::with_capacity()` will reserve space for up front
+///
+const MAX_RESERVED_STATES: usize = 65536;
 
 ///
 /// Represents a non-deterministic finite-state automata
@@ -71,6 +80,20 @@ impl<InputSymbol: Clone, OutputSymbol> Ndfa<InputSymbol, OutputSymbol> {
         Ndfa { max_state: 0, transitions: vec![], joined_with: vec![], output_symbols: HashMap::new() }
     }
 
+    ///
+    /// Creates a new non-deterministic finite automaton with space reserved for a certain number of states
+    ///
+    /// The NDFA will be able to store transitions and joins for up to `states` states without needing to reallocate.
+    /// `Pattern::estimated_states()` can be used to find a suitable size for an NDFA that a pattern will be compiled into.
+    /// The size is only treated as a hint: no more than 65536 states are reserved, so a very large estimate won't try
+    /// to allocate more memory than is available before any states have been added.
+    ///
+    pub fn with_capacity(states: usize) -> Ndfa<InputSymbol, OutputSymbol> {
+        let states = states.min(MAX_RESERVED_STATES);
+
+        Ndfa { max_state: 0, transitions: Vec::with_capacity(states), joined_with: Vec::with_capacity(states), output_symbols: HashMap::new() }
+    }
+
     ///
     /// Retrieves the complete set of states whose transitions should be returned due to joining for a given state
     ///
@@ -103,6 +126,92 @@ impl<InputSymbol: Clone, OutputSymbol> Ndfa<InputSymbol, OutputSymbol> {
 
         result
     }
+
+    ///
+    /// Iterates over the states that have an output symbol set directly on them, along with their output symbols
+    ///
+    /// Unlike `StateMachine::accepting_states()`, this does not consider joins, so states that only accept because they are
+    /// joined to an accepting state are not included. It also avoids building a map. The order that the states are returned
+    /// in is unspecified.
+    ///
+    pub fn output_symbols_iter<'a>(&'a self) -> impl Iterator<Item=(StateId, &'a OutputSymbol)>+'a {
+        self.output_symbols.iter().map(|(state, output)| (*state, output))
+    }
+
+    ///
+    /// Retrieves the output symbol set directly on a particular state
+    ///
+    /// Unlike `output_symbol_for_state()`, this does not consider any states that are joined to the specified state.
+    ///
+    pub fn get_output(&self, state: StateId) -> Option<&OutputSymbol> {
+        self.output_symbols.get(&state)
+    }
+}
+
+///
+/// True if two lists contain the same items, regardless of the order they appear in
+///
+fn same_items<Item: PartialEq>(first: &[Item], second: &[Item]) -> bool {
+    if first.len() != second.len() {
+        return false;
+    }
+
+    // Each item in the first list must be matched by a different item in the second list
+    let mut used = vec![false; second.len()];
+
+    for item in first {
+        let matching = (0..second.len()).find(|&index| !used[index] && second[index] == *item);
+
+        match matching {
+            Some(index) => used[index] = true,
+            None        => return false
+        }
+    }
+
+    true
+}
+
+impl<InputSymbol: Clone+PartialEq, OutputSymbol: PartialEq> Ndfa<InputSymbol, OutputSymbol> {
+    ///
+    /// Returns true if this NDFA has exactly the same states, transitions, joins and output symbols as another NDFA
+    ///
+    /// The order that transitions and joins were added in is not considered. This compares the structure of the two
+    /// NDFAs and not the language they match: two NDFAs built in different ways can match the same language without
+    /// being structurally equal.
+    ///
+    pub fn structurally_equal(&self, other: &Ndfa<InputSymbol, OutputSymbol>) -> bool {
+        if self.max_state != other.max_state {
+            return false;
+        }
+
+        for state in 0..(self.max_state as usize)+1 {
+            // Transitions are compared without regard for order
+            let our_transitions     = self.transitions.get(state).map(|transitions| &transitions[..]).unwrap_or(&[]);
+            let their_transitions   = other.transitions.get(state).map(|transitions| &transitions[..]).unwrap_or(&[]);
+
+            if !same_items(our_transitions, their_transitions) {
+                return false;
+            }
+
+            // As are the joined states
+            let mut our_joins   = self.joined_with.get(state).map(|joins| joins.to_vec()).unwrap_or_default();
+            let mut their_joins = other.joined_with.get(state).map(|joins| joins.to_vec()).unwrap_or_default();
+
+            our_joins.sort();
+            their_joins.sort();
+
+            if our_joins != their_joins {
+                return false;
+            }
+
+            // Output symbols must match
+            if self.output_symbols.get(&(state as StateId)) != other.output_symbols.get(&(state as StateId)) {
+                return false;
+            }
+        }
+
+        true
+    }
 }
 
 impl<Symbol: Ord+Clone+Countable, OutputSymbol> Ndfa<SymbolRange<Symbol>, OutputSymbol> {
@@ -112,10 +221,14 @@ impl<Symbol: Ord+Clone+Countable, OutputSymbol> Ndfa<SymbolRange<Symbol>, Output
     pub fn fix_overlapping_ranges(&mut self) {
         // TODO: this forces us to fix overlapping ranges every time we generate an NDFA, rather than before use
         // We'd like to fix before use to allow for things like merged state machines
+        self.fix_overlapping_ranges_using(&mut SymbolMap::new());
+    }
 
+    ///
+    /// Modifies this NDFA so that all ranges used in all transitions are unique, using an existing (empty) symbol map to gather the ranges
+    ///
+    pub fn fix_overlapping_ranges_using(&mut self, symbol_map: &mut SymbolMap<Symbol>) {
         // Gather all of the symbols in a map
-        let mut symbol_map = SymbolMap::new();
-
         for transit in &self.transitions {
             for &(ref range, _) in transit {
                 symbol_map.add_range(range);
@@ -143,6 +256,102 @@ impl<Symbol: Ord+Clone+Countable, OutputSymbol> Ndfa<SymbolRange<Symbol>, Output
 
         self.transitions = new_transitions;
     }
+
+    ///
+    /// Adds a transition that will be followed for any symbol between `lowest` and `highest` (inclusive)
+    ///
+    #[inline]
+    pub fn add_range_transition(&mut self, state: StateId, lowest: Symbol, highest: Symbol, new_state: StateId) {
+        self.add_transition(state, SymbolRange::new(lowest, highest), new_state);
+    }
+
+    ///
+    /// Adds a transition that will be followed for a single symbol
+    ///
+    #[inline]
+    pub fn add_symbol_transition(&mut self, state: StateId, symbol: Symbol, new_state: StateId) {
+        self.add_transition(state, SymbolRange::new(symbol.clone(), symbol), new_state);
+    }
+
+    ///
+    /// Compiles this NDFA into a DFA, reusing the scratch space in a compiler context
+    ///
+    /// This is useful when compiling a lot of NDFAs, as the context avoids needing to allocate new working space for each one.
+    ///
+    pub fn compile_with_context(self, context: &mut CompilerContext<Symbol>) -> SymbolRangeDfa<Symbol, OutputSymbol>
+    where OutputSymbol: Ord+Clone {
+        context.compile(self)
+    }
+}
+
+impl<Symbol: Ord+Clone, OutputSymbol> Ndfa<SymbolRange<Symbol>, OutputSymbol> {
+    ///
+    /// Converts this NDFA into one with no overlapping ranges, for symbols that are not `Countable`
+    ///
+    /// The ranges in the result use `OrdSymbol` so that the boundaries where ranges are split can be represented using
+    /// only comparisons. The result can be compiled into a DFA in the usual way, but input symbols must be passed to it
+    /// wrapped as `OrdSymbol::At(symbol)`.
+    ///
+    pub fn fix_overlapping_ranges_ord_only(self) -> Ndfa<SymbolRange<OrdSymbol<Symbol>>, OutputSymbol> {
+        // Every range becomes a range between the 'At' symbols for its boundaries
+        let transitions = self.transitions.into_iter()
+            .map(|transit| {
+                transit.into_iter()
+                    .map(|(range, state)| (SymbolRange::new(OrdSymbol::At(range.lowest), OrdSymbol::At(range.highest)), state))
+                    .collect()
+            })
+            .collect();
+
+        let mut result = Ndfa { 
+            max_state:      self.max_state, 
+            transitions:    transitions, 
+            joined_with:    self.joined_with, 
+            output_symbols: self.output_symbols 
+        };
+
+        // OrdSymbols are countable, so the ranges can be split in the usual way
+        result.fix_overlapping_ranges();
+
+        result
+    }
+}
+
+impl<Symbol: PartialOrd+Clone, OutputSymbol> Ndfa<BoundedRange<Symbol>, OutputSymbol> {
+    ///
+    /// Modifies this NDFA so that none of the ranges used in transitions overlap
+    ///
+    /// This is the equivalent of `fix_overlapping_ranges` for NDFAs that use `BoundedRange`s: as the ranges can have
+    /// exclusive boundaries, they can be split using only comparisons, so the symbols don't need to be `Countable`.
+    ///
+    pub fn fix_overlapping_ranges_bounded(&mut self) {
+        // Gather all of the ranges
+        let mut all_ranges = vec![];
+
+        for transit in &self.transitions {
+            for &(ref range, _) in transit {
+                all_ranges.push(range.clone());
+            }
+        }
+
+        // Split them so there are no overlaps
+        let no_overlapping = BoundedRange::split_overlapping(&all_ranges);
+
+        // Each transition is replaced with the pieces that make up its range
+        let mut new_transitions = vec![];
+
+        for transit in &self.transitions {
+            let without_overlapping: Vec<(BoundedRange<Symbol>, StateId)> = transit.iter()
+                .flat_map(|&(ref range, state)| {
+                    no_overlapping.iter()
+                        .filter(move |piece| range.contains_range(piece))
+                        .map(move |piece| (piece.clone(), state))
+                })
+                .collect();
+            new_transitions.push(without_overlapping);
+        }
+
+        self.transitions = new_transitions;
+    }
 }
 
 impl<InputSymbol: Clone, OutputSymbol> StateMachine<InputSymbol, OutputSymbol> for Ndfa<InputSymbol, OutputSymbol> {
@@ -266,6 +475,7 @@ impl<InputSymbol : Clone, OutputSymbol> MutableStateMachine<InputSymbol, OutputS
 #[cfg(test)]
 mod test {
     use super::super::state_machine::*;
+    use super::super::regular_pattern::*;
     use super::*;
 
     #[test]
@@ -337,6 +547,49 @@ mod test {
         assert!(ndfa.output_symbol_for_state(1) == None);
     }
 
+    #[test]
+    fn output_symbols_iter_reports_output_states() {
+        let mut ndfa: Ndfa<u32, u32> = Ndfa::new();
+
+        ndfa.add_transition(0, 1, 1);
+        ndfa.add_transition(1, 2, 2);
+        ndfa.add_transition(2, 3, 3);
+        ndfa.set_output_symbol(1, 10);
+        ndfa.set_output_symbol(3, 30);
+
+        let mut accepting: Vec<_> = ndfa.output_symbols_iter().map(|(state, output)| (state, *output)).collect();
+        accepting.sort();
+
+        assert!(accepting == vec![(1, 10), (3, 30)]);
+    }
+
+    #[test]
+    fn get_output_ignores_joined_states() {
+        let mut ndfa: Ndfa<u32, u32> = Ndfa::new();
+
+        ndfa.add_transition(0, 1, 1);
+        ndfa.add_transition(1, 2, 2);
+        ndfa.set_output_symbol(2, 20);
+        ndfa.join_states(1, 2);
+
+        assert!(ndfa.get_output(2) == Some(&20));
+        assert!(ndfa.get_output(1) == None);
+        assert!(ndfa.output_symbol_for_state(1) == Some(&20));
+        assert!(ndfa.output_symbols_iter().count() == 1);
+        assert!(StateMachine::accepting_states(&ndfa).len() == 2);
+    }
+
+    #[test]
+    fn transition_target_finds_matching_symbol() {
+        let mut ndfa: Ndfa<u32, u32> = Ndfa::new();
+
+        ndfa.add_transition(0, 1, 1);
+        ndfa.add_transition(0, 2, 2);
+
+        assert!(ndfa.transition_target(0, &2) == Some(2));
+        assert!(ndfa.transition_target(0, &3) == None);
+    }
+
     #[test]
     fn can_set_output_symbol() {
         let mut ndfa: Ndfa<u32, u32> = Ndfa::new();
@@ -422,4 +675,175 @@ mod test {
         assert!(ndfa.get_transitions_for_state(1).contains(&(42, 1)));
         assert!(ndfa.get_transitions_for_state(1).contains(&(43, 2)));
     }
+
+    #[test]
+    fn ndfas_from_same_pattern_are_structurally_equal() {
+        let pattern = exactly("abc").or("def").repeat_forever(1);
+
+        let mut first: Ndfa<SymbolRange<char>, u32>  = Ndfa::new();
+        let mut second: Ndfa<SymbolRange<char>, u32> = Ndfa::new();
+
+        let first_end   = pattern.compile(&mut first, 0);
+        let second_end  = pattern.compile(&mut second, 0);
+
+        first.set_output_symbol(first_end, 1);
+        second.set_output_symbol(second_end, 1);
+
+        assert!(first.structurally_equal(&second));
+    }
+
+    #[test]
+    fn transition_order_does_not_affect_structural_equality() {
+        let mut first: Ndfa<u32, u32>  = Ndfa::new();
+        let mut second: Ndfa<u32, u32> = Ndfa::new();
+
+        first.add_transition(0, 1, 1);
+        first.add_transition(0, 2, 2);
+        second.add_transition(0, 2, 2);
+        second.add_transition(0, 1, 1);
+
+        assert!(first.structurally_equal(&second));
+    }
+
+    #[test]
+    fn ndfas_from_different_patterns_are_not_structurally_equal() {
+        let mut first: Ndfa<SymbolRange<char>, u32>  = Ndfa::new();
+        let mut second: Ndfa<SymbolRange<char>, u32> = Ndfa::new();
+
+        exactly("abc").compile(&mut first, 0);
+        exactly("abd").compile(&mut second, 0);
+
+        assert!(!first.structurally_equal(&second));
+    }
+
+    #[test]
+    fn different_output_symbols_are_not_structurally_equal() {
+        let mut first: Ndfa<u32, u32>  = Ndfa::new();
+        let mut second: Ndfa<u32, u32> = Ndfa::new();
+
+        first.add_transition(0, 1, 1);
+        second.add_transition(0, 1, 1);
+        first.set_output_symbol(1, 1);
+        second.set_output_symbol(1, 2);
+
+        assert!(!first.structurally_equal(&second));
+    }
+
+    #[test]
+    fn presized_ndfa_does_not_reallocate_transitions() {
+        let pattern     = exactly(&(0..100).collect::<Vec<u32>>());
+        let mut ndfa    = Ndfa::<SymbolRange<u32>, ()>::with_capacity(pattern.estimated_states()+1);
+
+        let initial_capacity = ndfa.transitions.capacity();
+        pattern.compile(&mut ndfa, 0);
+
+        assert!(ndfa.transitions.len() == 100);
+        assert!(ndfa.transitions.capacity() == initial_capacity);
+    }
+
+    #[test]
+    fn huge_capacity_is_limited() {
+        let pattern = MatchRange(0u32, 9).repeat(0..65536).repeat(0..65536).repeat(0..65536).repeat(0..65536).repeat(0..65536);
+        let ndfa    = Ndfa::<SymbolRange<u32>, ()>::with_capacity(pattern.estimated_states());
+
+        assert!(pattern.estimated_states() == usize::MAX);
+        assert!(ndfa.transitions.capacity() <= MAX_RESERVED_STATES);
+        assert!(ndfa.joined_with.capacity() <= MAX_RESERVED_STATES);
+    }
+
+    #[test]
+    fn can_fix_overlapping_bounded_ranges() {
+        let mut ndfa: Ndfa<BoundedRange<f64>, ()> = Ndfa::new();
+
+        ndfa.add_transition(0, BoundedRange::inclusive(0.0, 5.0), 1);
+        ndfa.add_transition(0, BoundedRange::inclusive(3.0, 10.0), 2);
+        ndfa.fix_overlapping_ranges_bounded();
+
+        let transitions = ndfa.get_transitions_for_state(0);
+
+        assert!(transitions.len() == 4);
+        assert!(transitions.iter().filter(|&&(_, state)| state == 1).count() == 2);
+        assert!(transitions.iter().filter(|&&(_, state)| state == 2).count() == 2);
+
+        // 4.0 is in both ranges, so should be in the same piece for both targets
+        let targets: Vec<_> = transitions.iter().filter(|&&(ref range, _)| range.includes(&4.0)).map(|&(_, state)| state).collect();
+        assert!(targets == vec![1, 2]);
+    }
+
+    #[test]
+    fn range_transition_is_same_as_explicit_range() {
+        let mut explicit: Ndfa<SymbolRange<u32>, u32>   = Ndfa::new();
+        let mut convenient: Ndfa<SymbolRange<u32>, u32> = Ndfa::new();
+
+        explicit.add_transition(0, SymbolRange::new(1, 10), 1);
+        convenient.add_range_transition(0, 1, 10, 1);
+
+        assert!(convenient.count_states() == explicit.count_states());
+        assert!(convenient.get_transitions_for_state(0) == explicit.get_transitions_for_state(0));
+        assert!(convenient.get_transitions_for_state(0) == vec![(SymbolRange::new(1, 10), 1)]);
+    }
+
+    #[test]
+    fn symbol_transition_is_same_as_explicit_range() {
+        let mut explicit: Ndfa<SymbolRange<char>, u32>   = Ndfa::new();
+        let mut convenient: Ndfa<SymbolRange<char>, u32> = Ndfa::new();
+
+        explicit.add_transition(0, SymbolRange::new('a', 'a'), 1);
+        explicit.add_transition(1, SymbolRange::new('b', 'b'), 2);
+        convenient.add_symbol_transition(0, 'a', 1);
+        convenient.add_symbol_transition(1, 'b', 2);
+
+        assert!(convenient.count_states() == explicit.count_states());
+        assert!(convenient.get_transitions_for_state(0) == explicit.get_transitions_for_state(0));
+        assert!(convenient.get_transitions_for_state(1) == explicit.get_transitions_for_state(1));
+        assert!(convenient.get_transitions_for_state(1) == vec![(SymbolRange::new('b', 'b'), 2)]);
+    }
+
+    #[test]
+    fn can_match_symbols_that_are_not_countable() {
+        use std::cmp::Ordering;
+        use super::super::*;
+
+        // Floating point value that is ordered but not countable
+        #[derive(Clone, PartialEq, Debug)]
+        struct Float(f64);
+
+        impl Eq for Float { }
+        impl PartialOrd for Float {
+            fn partial_cmp(&self, other: &Float) -> Option<Ordering> { Some(self.cmp(other)) }
+        }
+        impl Ord for Float {
+            fn cmp(&self, other: &Float) -> Ordering { self.0.partial_cmp(&other.0).unwrap() }
+        }
+
+        // Two overlapping ranges
+        let mut ndfa = Ndfa::new();
+
+        let low_end  = MatchRange(Float(0.0), Float(5.0)).append(exactly(&vec![Float(100.0)]).repeat(0..2)).compile(&mut ndfa, 0);
+        let high_end = MatchRange(Float(3.0), Float(10.0)).compile(&mut ndfa, 0);
+
+        ndfa.set_output_symbol(low_end, 1);
+        ndfa.set_output_symbol(high_end, 2);
+
+        let ndfa    = ndfa.fix_overlapping_ranges_ord_only();
+        let dfa     = DfaCompiler::build(ndfa, SymbolRangeDfaBuilder::new());
+
+        let match_floats = |floats: Vec<f64>| {
+            let mut reader = VecReader::from_vec(floats).map_symbols(|f| OrdSymbol::At(Float(f)));
+            match match_pattern(dfa.start(), &mut reader) {
+                Accept(length, output)  => Some((length, *output)),
+                _                       => None
+            }
+        };
+
+        assert!(match_floats(vec![1.0]) == Some((1, 1)));
+        assert!(match_floats(vec![4.5]) == Some((1, 1)));
+        assert!(match_floats(vec![5.0]) == Some((1, 1)));
+        assert!(match_floats(vec![5.5]) == Some((1, 2)));
+        assert!(match_floats(vec![10.0]) == Some((1, 2)));
+        assert!(match_floats(vec![3.0, 100.0]) == Some((2, 1)));
+        assert!(match_floats(vec![7.0, 100.0]) == Some((1, 2)));
+        assert!(match_floats(vec![-1.0]) == None);
+        assert!(match_floats(vec![10.5]) == None);
+    }
 }
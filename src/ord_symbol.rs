@@ -0,0 +1,138 @@
+//
+//   Copyright 2016 Andrew Hunter
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+//
+
+//!
+//! Splitting overlapping symbol ranges needs to know the symbol that follows or precedes a range boundary, which is why
+//! the DFA compiler requires symbols to be `Countable`. Some symbol types (floating point values, or opaque tokens that are
+//! only ordered) have no such neighbours.
+//!
+//! `OrdSymbol` makes any ordered symbol countable by treating range boundaries symbolically: every symbol `x` gains two
+//! neighbours, `Before(x)` and `After(x)`, which sort immediately below and above it. A range that was split at `x` can then be
+//! represented as ending at `Before(x)` or starting at `After(x)` without needing to know what the real neighbouring symbol is.
+//!
+//! Input symbols should always be supplied as `OrdSymbol::At(x)` when matching against a state machine built from these
+//! symbols.
+//!
+
+use std::cmp::*;
+
+use super::countable::*;
+
+///
+/// Wraps an ordered symbol so that it can be used where a `Countable` symbol is required
+///
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum OrdSymbol<Symbol: Ord> {
+    /// Sorts after every symbol less than the wrapped symbol, but before the symbol itself
+    Before(Symbol),
+
+    /// The wrapped symbol
+    At(Symbol),
+
+    /// Sorts after the wrapped symbol, but before every symbol greater than it
+    After(Symbol)
+}
+
+use self::OrdSymbol::*;
+
+impl<Symbol: Ord> OrdSymbol<Symbol> {
+    ///
+    /// Retrieves the symbol that this boundary is next to
+    ///
+    #[inline]
+    pub fn symbol(&self) -> &Symbol {
+        match self {
+            &Before(ref symbol) => symbol,
+            &At(ref symbol)     => symbol,
+            &After(ref symbol)  => symbol
+        }
+    }
+
+    ///
+    /// Where this symbol sorts relative to the other symbols next to the same base symbol
+    ///
+    #[inline]
+    fn rank(&self) -> u8 {
+        match self {
+            &Before(_)  => 0,
+            &At(_)      => 1,
+            &After(_)   => 2
+        }
+    }
+}
+
+impl<Symbol: Ord> PartialOrd for OrdSymbol<Symbol> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<Symbol: Ord> Ord for OrdSymbol<Symbol> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let symbol_order = self.symbol().cmp(other.symbol());
+
+        if symbol_order == Ordering::Equal {
+            self.rank().cmp(&other.rank())
+        } else {
+            symbol_order
+        }
+    }
+}
+
+///
+/// Range splitting only ever asks for the symbol after the highest symbol in a range or the symbol before the lowest
+/// symbol in a range, so `next()` is only defined for `Before` and `At` and `prev()` is only defined for `At` and `After`.
+/// The undefined cases return the symbol unchanged.
+///
+impl<Symbol: Ord+Clone> Countable for OrdSymbol<Symbol> {
+    fn next(&self) -> Self {
+        match self {
+            &Before(ref symbol) => At(symbol.clone()),
+            &At(ref symbol)     => After(symbol.clone()),
+            &After(ref symbol)  => After(symbol.clone())
+        }
+    }
+
+    fn prev(&self) -> Self {
+        match self {
+            &Before(ref symbol) => Before(symbol.clone()),
+            &At(ref symbol)     => Before(symbol.clone()),
+            &After(ref symbol)  => At(symbol.clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn boundaries_sort_around_symbol() {
+        assert!(Before(2) < At(2));
+        assert!(At(2) < After(2));
+        assert!(After(1) < Before(2));
+        assert!(After(2) < Before(3));
+    }
+
+    #[test]
+    fn next_and_prev_move_between_boundaries() {
+        assert!(At(2).next() == After(2));
+        assert!(At(2).prev() == Before(2));
+        assert!(Before(2).next() == At(2));
+        assert!(After(2).prev() == At(2));
+    }
+}
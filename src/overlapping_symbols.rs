@@ -93,6 +93,41 @@ impl<Symbol: Ord+Clone+Countable> SymbolMap<Symbol> {
         }
     }
 
+    ///
+    /// Removes all of the ranges from this map (keeping the memory allocated for them)
+    ///
+    pub fn clear(&mut self) {
+        self.ranges.clear();
+    }
+
+    ///
+    /// Removes a range that was previously added to this object
+    ///
+    /// Only a range exactly matching the specified range is removed: other ranges that overlap it are left alone.
+    ///
+    pub fn remove_range(&mut self, range: &SymbolRange<Symbol>) {
+        let existing = self.ranges.binary_search_by(|test_range| { SymbolMap::order_ranges(test_range, range) });
+
+        if let Ok(existing_pos) = existing {
+            self.ranges.remove(existing_pos);
+        }
+    }
+
+    ///
+    /// True if exactly the specified range has been added to this object
+    ///
+    pub fn contains(&self, range: &SymbolRange<Symbol>) -> bool {
+        self.ranges.binary_search_by(|test_range| { SymbolMap::order_ranges(test_range, range) }).is_ok()
+    }
+
+    ///
+    /// Returns the ranges in this map, in order
+    ///
+    #[inline]
+    pub fn ranges(&self) -> &[SymbolRange<Symbol>] {
+        &self.ranges
+    }
+
     ///
     /// Finds the ranges in this map that overlap the target ranges
     ///
@@ -217,6 +252,37 @@ mod test {
         assert!(mid == vec![&SymbolRange::new(0, 4)]);
     }
 
+    #[test]
+    fn can_remove_range() {
+        let mut map = SymbolMap::new();
+
+        map.add_range(&SymbolRange::new(0, 4));
+        map.add_range(&SymbolRange::new(5, 10));
+        map.add_range(&SymbolRange::new(11, 15));
+
+        assert!(map.contains(&SymbolRange::new(5, 10)));
+
+        map.remove_range(&SymbolRange::new(5, 10));
+
+        assert!(!map.contains(&SymbolRange::new(5, 10)));
+        assert!(map.contains(&SymbolRange::new(0, 4)));
+        assert!(map.contains(&SymbolRange::new(11, 15)));
+
+        let all = map.find_overlapping_ranges(&SymbolRange::new(0, 15));
+        assert!(all == vec![&SymbolRange::new(0, 4), &SymbolRange::new(11, 15)]);
+    }
+
+    #[test]
+    fn removing_overlapping_range_leaves_map_unchanged() {
+        let mut map = SymbolMap::new();
+
+        map.add_range(&SymbolRange::new(0, 4));
+        map.remove_range(&SymbolRange::new(0, 3));
+
+        assert!(map.contains(&SymbolRange::new(0, 4)));
+        assert!(!map.contains(&SymbolRange::new(0, 3)));
+    }
+
     #[test]
     fn works_with_duplicate_lower_values() {
         let mut map = SymbolMap::new();
@@ -0,0 +1,146 @@
+//
+//   Copyright 2016 Andrew Hunter
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+//
+
+//!
+//! Compiling a pattern into a DFA is much slower than matching against it, so programs that prepare the same patterns over
+//! and over again (for instance, once per request) can use a `PatternCache` to only compile each pattern once:
+//!
+//! ```
+//! # use concordance::*;
+//! let mut cache   = PatternCache::new();
+//! let dfa         = cache.get_or_compile(&exactly("abc").repeat_forever(1));
+//!
+//! # assert!(matches_rc("abcabc", dfa) == Some(6));
+//! ```
+//!
+
+use std::rc::Rc;
+use std::hash::Hash;
+use std::collections::HashMap;
+
+use super::countable::*;
+use super::state_machine::*;
+use super::prepare::*;
+use super::regular_pattern::*;
+use super::symbol_range_dfa::*;
+
+///
+/// Stores the DFAs compiled from a set of patterns so they can be reused
+///
+pub struct PatternCache<Symbol: Clone+Ord+Hash, OutputSymbol> {
+    /// The output symbol used for the accepting states of the compiled DFAs
+    output: OutputSymbol,
+
+    /// The DFAs that have been compiled so far
+    compiled: HashMap<Pattern<Symbol>, Rc<SymbolRangeDfa<Symbol, OutputSymbol>>>
+}
+
+impl<Symbol: Clone+Ord+Hash+Countable+'static> PatternCache<Symbol, ()> {
+    ///
+    /// Creates a new, empty pattern cache
+    ///
+    pub fn new() -> PatternCache<Symbol, ()> {
+        PatternCache::with_output_symbol(())
+    }
+}
+
+impl<Symbol: Clone+Ord+Hash+Countable+'static> Default for PatternCache<Symbol, ()> {
+    fn default() -> PatternCache<Symbol, ()> {
+        PatternCache::new()
+    }
+}
+
+impl<Symbol: Clone+Ord+Hash+Countable+'static, OutputSymbol: Clone+Ord+'static> PatternCache<Symbol, OutputSymbol> {
+    ///
+    /// Creates a new, empty pattern cache where the compiled DFAs produce the specified output symbol when they match
+    ///
+    pub fn with_output_symbol(output: OutputSymbol) -> PatternCache<Symbol, OutputSymbol> {
+        PatternCache { output: output, compiled: HashMap::new() }
+    }
+
+    ///
+    /// Retrieves the DFA for a pattern, compiling it if it's not already in the cache
+    ///
+    pub fn get_or_compile(&mut self, pattern: &Pattern<Symbol>) -> Rc<SymbolRangeDfa<Symbol, OutputSymbol>> {
+        if let Some(dfa) = self.compiled.get(pattern) {
+            return Rc::clone(dfa);
+        }
+
+        let dfa = Rc::new(pattern.to_ndfa(self.output.clone()).prepare_to_match());
+        self.compiled.insert(pattern.clone(), Rc::clone(&dfa));
+
+        dfa
+    }
+
+    ///
+    /// Returns the number of patterns that have been compiled by this cache
+    ///
+    pub fn len(&self) -> usize {
+        self.compiled.len()
+    }
+
+    ///
+    /// True if no patterns have been compiled by this cache
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.compiled.is_empty()
+    }
+
+    ///
+    /// Removes all of the compiled patterns from this cache
+    ///
+    pub fn clear(&mut self) {
+        self.compiled.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::matches::*;
+    use super::super::symbol_reader::*;
+
+    #[test]
+    fn second_lookup_returns_cached_dfa() {
+        let mut cache   = PatternCache::new();
+        let first       = cache.get_or_compile(&exactly("abc").repeat_forever(1));
+        let second      = cache.get_or_compile(&exactly("abc").repeat_forever(1));
+
+        assert!(Rc::ptr_eq(&first, &second));
+        assert!(cache.len() == 1);
+        assert!(matches_rc("abcabc", second) == Some(6));
+    }
+
+    #[test]
+    fn different_patterns_are_compiled_separately() {
+        let mut cache   = PatternCache::new();
+        let first       = cache.get_or_compile(&exactly("abc"));
+        let second      = cache.get_or_compile(&exactly("def"));
+
+        assert!(!Rc::ptr_eq(&first, &second));
+        assert!(cache.len() == 2);
+        assert!(matches_rc("def", second) == Some(3));
+        assert!(matches_rc("def", first) == None);
+    }
+
+    #[test]
+    fn can_cache_with_output_symbol() {
+        let mut cache   = PatternCache::with_output_symbol("Number");
+        let dfa         = cache.get_or_compile(&MatchRange('0', '9').repeat_forever(1));
+
+        assert!(dfa.match_or_error(&mut "123".read_symbols()) == Ok((3, &"Number")));
+    }
+}
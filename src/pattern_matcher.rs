@@ -44,7 +44,7 @@ pub trait PatternMatcher<InputSymbol, OutputSymbol> {
 ///
 /// Action to be taken after a matcher receives a symbol
 ///
-pub enum MatchAction<'a, OutputSymbol: 'static, State: Sized> {
+pub enum MatchAction<'a, OutputSymbol: 'a, State: Sized> {
     // State is also always: MatchingState<InputSymbol, OutputSymbol> (important to know that as its how More is used)
     //
     // However, rust complains that InputSymbol is unused if we declare it in MatchAction and that it is undeclared if we don't
@@ -62,7 +62,7 @@ pub enum MatchAction<'a, OutputSymbol: 'static, State: Sized> {
     More(State)
 }
 
-impl<'a, OutputSymbol: PartialEq+'static, State: Sized> MatchAction<'a, OutputSymbol, State> {
+impl<'a, OutputSymbol: PartialEq+'a, State: Sized> MatchAction<'a, OutputSymbol, State> {
     ///
     /// Returns true if this is an accepting state with the specified symbol
     ///
@@ -74,6 +74,39 @@ impl<'a, OutputSymbol: PartialEq+'static, State: Sized> MatchAction<'a, OutputSy
     }
 }
 
+impl<'a, OutputSymbol: 'a, State: Sized> MatchAction<'a, OutputSymbol, State> {
+    ///
+    /// Returns true if this is a rejecting state
+    ///
+    pub fn is_reject(&self) -> bool {
+        match self {
+            &Reject => true,
+            _ => false
+        }
+    }
+
+    ///
+    /// Returns the number of symbols that were matched if this is an accepting state
+    ///
+    pub fn accept_length(&self) -> Option<usize> {
+        match self {
+            &Accept(length, _) => Some(length),
+            _ => None
+        }
+    }
+
+    ///
+    /// Returns the length and output symbol of an accepting state, panicking if this is not an accepting state
+    ///
+    pub fn unwrap_accept(self) -> (usize, &'a OutputSymbol) {
+        match self {
+            Accept(length, symbol) => (length, symbol),
+            Reject => panic!("called `unwrap_accept()` on a `Reject` value"),
+            More(_) => panic!("called `unwrap_accept()` on a `More` value")
+        }
+    }
+}
+
 ///
 /// Represents a state during a pattern matching operation
 ///
@@ -89,4 +122,64 @@ pub trait MatchingState<'a, InputSymbol, OutputSymbol> : Sized {
     fn finish(self) -> MatchAction<'a, OutputSymbol, Self>;
 }
 
+///
+/// A matching state that can report whether it is accepting without finishing the match
+///
+/// This is needed to stop at the first accepting state rather than the last one (see `match_pattern_shortest()`).
+///
+pub trait PeekableMatchingState<'a, InputSymbol, OutputSymbol> : MatchingState<'a, InputSymbol, OutputSymbol> {
+    ///
+    /// If the current state is an accepting state, returns the number of symbols matched so far and the output symbol
+    ///
+    fn peek_accept(&self) -> Option<(usize, &'a OutputSymbol)>;
+}
+
 pub use MatchAction::*;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static OUTPUT: u32 = 42;
+
+    #[test]
+    fn accept_helpers() {
+        let accept: MatchAction<u32, ()> = Accept(3, &OUTPUT);
+
+        assert!(!accept.is_reject());
+        assert!(accept.accept_length() == Some(3));
+        assert!(accept.unwrap_accept() == (3, &42));
+    }
+
+    #[test]
+    fn reject_helpers() {
+        let reject: MatchAction<u32, ()> = Reject;
+
+        assert!(reject.is_reject());
+        assert!(reject.accept_length() == None);
+    }
+
+    #[test]
+    fn more_helpers() {
+        let more: MatchAction<u32, ()> = More(());
+
+        assert!(!more.is_reject());
+        assert!(more.accept_length() == None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn unwrap_reject_panics() {
+        let reject: MatchAction<u32, ()> = Reject;
+
+        reject.unwrap_accept();
+    }
+
+    #[test]
+    #[should_panic]
+    fn unwrap_more_panics() {
+        let more: MatchAction<u32, ()> = More(());
+
+        more.unwrap_accept();
+    }
+}
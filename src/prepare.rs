@@ -73,6 +73,43 @@ for &'a ToPattern<InputSymbol> {
     }
 }
 
+impl<'a, InputSymbol: Clone+Ord+Countable+'static> PrepareToMatch<SymbolRangeDfa<InputSymbol, bool>> 
+for &'a [Pattern<InputSymbol>] {
+    #[inline]
+    fn prepare_to_match(self) -> SymbolRangeDfa<InputSymbol, bool> {
+        let ndfa = self.to_ndfa(true);
+
+        ndfa.prepare_to_match()
+    }
+}
+
+impl<'a, InputSymbol: Clone+Ord+Countable+'static> PrepareToMatch<SymbolRangeDfa<InputSymbol, bool>> 
+for &'a Vec<Pattern<InputSymbol>> {
+    #[inline]
+    fn prepare_to_match(self) -> SymbolRangeDfa<InputSymbol, bool> {
+        self[..].prepare_to_match()
+    }
+}
+
+impl<'a, InputSymbol: Clone+Ord+Countable+'static> PrepareToMatch<SymbolRangeDfa<InputSymbol, ()>> 
+for &'a PatternDifference<InputSymbol> {
+    #[inline]
+    fn prepare_to_match(self) -> SymbolRangeDfa<InputSymbol, ()> {
+        let pattern: SymbolRangeDfa<InputSymbol, ()>    = self.pattern.to_ndfa(()).prepare_to_match();
+        let excluding: SymbolRangeDfa<InputSymbol, ()>  = self.excluding.to_ndfa(()).prepare_to_match();
+
+        pattern.difference(&excluding)
+    }
+}
+
+impl<InputSymbol: Clone+Ord+Countable+'static> PrepareToMatch<SymbolRangeDfa<InputSymbol, ()>> 
+for PatternDifference<InputSymbol> {
+    #[inline]
+    fn prepare_to_match(self) -> SymbolRangeDfa<InputSymbol, ()> {
+        (&self).prepare_to_match()
+    }
+}
+
 impl<InputSymbol: Clone+Ord+Countable, OutputSymbol> PrepareToMatch<SymbolRangeDfa<InputSymbol, OutputSymbol>> for SymbolRangeDfa<InputSymbol, OutputSymbol> {
     #[inline]
     fn prepare_to_match(self) -> SymbolRangeDfa<InputSymbol, OutputSymbol> {
@@ -80,6 +117,14 @@ impl<InputSymbol: Clone+Ord+Countable, OutputSymbol> PrepareToMatch<SymbolRangeD
     }
 }
 
+impl<'a, InputSymbol: Clone+Ord+Countable, OutputSymbol: Clone> PrepareToMatch<SymbolRangeDfa<InputSymbol, OutputSymbol>> 
+for &'a SymbolRangeDfa<InputSymbol, OutputSymbol> {
+    #[inline]
+    fn prepare_to_match(self) -> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+        self.clone()
+    }
+}
+
 impl<'a> PrepareToMatch<SymbolRangeDfa<char, ()>> 
 for &'a str {
     #[inline]
@@ -0,0 +1,551 @@
+//
+//   Copyright 2016 Andrew Hunter
+//
+//   Licensed under the Apache License, Version 2.0 (the "License");
+//   you may not use this file except in compliance with the License.
+//   You may obtain a copy of the License at
+//
+//       http://www.apache.org/licenses/LICENSE-2.0
+//
+//   Unless required by applicable law or agreed to in writing, software
+//   distributed under the License is distributed on an "AS IS" BASIS,
+//   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//   See the License for the specific language governing permissions and
+//   limitations under the License.
+//
+
+//!
+//! Parses regular expression strings into patterns.
+//!
+//! The result is an ordinary `Pattern<char>`, so it can be combined with other patterns, added to a tokenizer or
+//! prepared for matching in the same way as a pattern that was built by hand:
+//!
+//! ```
+//! # use concordance::*;
+//! let pattern = parse_regex("a(b|c)*d").unwrap();
+//!
+//! assert!(matches("abcbd", pattern) == Some(5));
+//! ```
+//!
+//! The supported syntax is:
+//!
+//! * Literal characters, and `.` to match any character except a newline
+//! * `*`, `+`, `?`, `{m}`, `{m,}` and `{m,n}` to repeat the previous item
+//! * `|` to match either of two alternatives, and `(...)` for grouping
+//! * Character classes such as `[a-z_]` and negated classes such as `[^0-9]`
+//! * Escapes: `\n`, `\r`, `\t`, `\d`, `\w`, `\s` and `\` followed by any other character to match it literally
+//!
+//! Anchors, backreferences and lazy repetition are not supported, as they can't be expressed as a pattern.
+//!
+
+use std::char;
+use std::fmt;
+use std::error::Error;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use super::regular_pattern::*;
+
+///
+/// Error that can occur while parsing a regular expression
+///
+/// Positions are the index of the character where the error was found.
+///
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RegexParseError {
+    /// The expression ended before a group, class, repetition or escape was closed
+    UnexpectedEndOfExpression,
+
+    /// A character appeared somewhere where it isn't allowed (eg, a ')' with no matching '(' or a '*' with nothing to repeat)
+    UnexpectedCharacter(usize, char),
+
+    /// A `{m,n}` repetition was badly formed or had `n` less than `m`
+    InvalidRepetition(usize),
+
+    /// A range in a character class had its end before its start
+    InvalidClassRange(usize)
+}
+
+impl fmt::Display for RegexParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &RegexParseError::UnexpectedEndOfExpression         => write!(f, "unexpected end of regular expression"),
+            &RegexParseError::UnexpectedCharacter(pos, chr)     => write!(f, "unexpected '{}' at position {}", chr, pos),
+            &RegexParseError::InvalidRepetition(pos)            => write!(f, "invalid repetition at position {}", pos),
+            &RegexParseError::InvalidClassRange(pos)            => write!(f, "invalid character class range at position {}", pos)
+        }
+    }
+}
+
+impl Error for RegexParseError {
+}
+
+///
+/// Parses a regular expression into a pattern
+///
+pub fn parse_regex(regex: &str) -> Result<Pattern<char>, RegexParseError> {
+    let mut parser  = RegexParser { chars: regex.chars().peekable(), pos: 0 };
+    let pattern     = parser.parse_alternatives()?;
+
+    // parse_alternatives stops at a ')' or the end of the string: a ')' here has no matching '('
+    match parser.next() {
+        None        => Ok(pattern),
+        Some(chr)   => Err(RegexParseError::UnexpectedCharacter(parser.pos-1, chr))
+    }
+}
+
+///
+/// Returns the character after the specified one, skipping the surrogate range
+///
+fn next_char(chr: char) -> Option<char> {
+    match chr as u32 {
+        0xd7ff  => Some('\u{e000}'),
+        code    => char::from_u32(code+1)
+    }
+}
+
+///
+/// Returns the character before the specified one, skipping the surrogate range
+///
+fn prev_char(chr: char) -> Option<char> {
+    match chr as u32 {
+        0       => None,
+        0xe000  => Some('\u{d7ff}'),
+        code    => char::from_u32(code-1)
+    }
+}
+
+///
+/// Returns the ranges of characters that are not in the specified set of ranges
+///
+fn negate_ranges(ranges: Vec<(char, char)>) -> Vec<(char, char)> {
+    let mut ranges = ranges;
+    ranges.sort();
+
+    let mut result          = vec![];
+    let mut next_unmatched  = Some('\u{0}');
+
+    for (lowest, highest) in ranges {
+        if let Some(gap_start) = next_unmatched {
+            if gap_start < lowest {
+                result.push((gap_start, prev_char(lowest).unwrap()));
+            }
+
+            if highest >= gap_start {
+                next_unmatched = next_char(highest);
+            }
+        }
+    }
+
+    if let Some(gap_start) = next_unmatched {
+        result.push((gap_start, char::MAX));
+    }
+
+    result
+}
+
+///
+/// Converts a set of character ranges into a pattern
+///
+fn pattern_for_ranges(ranges: Vec<(char, char)>) -> Pattern<char> {
+    let mut alternatives: Vec<Pattern<char>> = ranges.into_iter()
+        .map(|(lowest, highest)| if lowest == highest { Match(vec![lowest]) } else { MatchRange(lowest, highest) })
+        .collect();
+
+    if alternatives.len() == 1 {
+        alternatives.pop().unwrap()
+    } else {
+        MatchAny(alternatives)
+    }
+}
+
+///
+/// Returns the character ranges for a class escape such as `\d`, or None if the character isn't a class escape
+///
+fn class_escape(chr: char) -> Option<Vec<(char, char)>> {
+    match chr {
+        'd' => Some(vec![('0', '9')]),
+        'w' => Some(vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')]),
+        's' => Some(vec![('\t', '\n'), ('\r', '\r'), (' ', ' ')]),
+        'D' => Some(negate_ranges(vec![('0', '9')])),
+        'W' => Some(negate_ranges(vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')])),
+        'S' => Some(negate_ranges(vec![('\t', '\n'), ('\r', '\r'), (' ', ' ')])),
+        _   => None
+    }
+}
+
+///
+/// Returns the character matched by a literal escape such as `\n`
+///
+fn literal_escape(chr: char) -> char {
+    match chr {
+        'n'     => '\n',
+        'r'     => '\r',
+        't'     => '\t',
+        '0'     => '\0',
+        other   => other
+    }
+}
+
+///
+/// Recursive descent parser for regular expressions
+///
+struct RegexParser<'a> {
+    /// The characters remaining in the expression
+    chars: Peekable<Chars<'a>>,
+
+    /// The index of the next character to be read
+    pos: usize
+}
+
+impl<'a> RegexParser<'a> {
+    ///
+    /// Reads the next character from the expression
+    ///
+    fn next(&mut self) -> Option<char> {
+        let result = self.chars.next();
+        if result.is_some() { self.pos += 1; }
+        result
+    }
+
+    ///
+    /// Reads the next character from the expression, failing if the expression has ended
+    ///
+    fn expect_next(&mut self) -> Result<char, RegexParseError> {
+        self.next().ok_or(RegexParseError::UnexpectedEndOfExpression)
+    }
+
+    ///
+    /// Parses a set of alternatives separated by '|', stopping at a ')' or the end of the expression
+    ///
+    fn parse_alternatives(&mut self) -> Result<Pattern<char>, RegexParseError> {
+        let mut alternatives = vec![self.parse_sequence()?];
+
+        while self.chars.peek() == Some(&'|') {
+            self.next();
+            alternatives.push(self.parse_sequence()?);
+        }
+
+        if alternatives.len() == 1 {
+            Ok(alternatives.pop().unwrap())
+        } else {
+            Ok(MatchAny(alternatives))
+        }
+    }
+
+    ///
+    /// Parses a sequence of repeated items, stopping at a '|', a ')' or the end of the expression
+    ///
+    fn parse_sequence(&mut self) -> Result<Pattern<char>, RegexParseError> {
+        let mut sequence: Vec<Pattern<char>> = vec![];
+
+        loop {
+            match self.chars.peek() {
+                None | Some(&'|') | Some(&')') => break,
+                _ => { }
+            }
+
+            let item = self.parse_repeat()?;
+
+            // Runs of literal characters are combined into a single match
+            match (sequence.pop(), item) {
+                (Some(Match(mut first)), Match(second)) => {
+                    first.extend(second);
+                    sequence.push(Match(first));
+                },
+
+                (Some(previous), item) => {
+                    sequence.push(previous);
+                    sequence.push(item);
+                },
+
+                (None, item) => sequence.push(item)
+            }
+        }
+
+        match sequence.len() {
+            0 => Ok(Epsilon),
+            1 => Ok(sequence.pop().unwrap()),
+            _ => Ok(MatchAll(sequence))
+        }
+    }
+
+    ///
+    /// Parses an item followed by any number of repetition operators
+    ///
+    fn parse_repeat(&mut self) -> Result<Pattern<char>, RegexParseError> {
+        let mut item = self.parse_item()?;
+
+        loop {
+            item = match self.chars.peek() {
+                Some(&'*') => { self.next(); RepeatInfinite(0, Box::new(item)) },
+                Some(&'+') => { self.next(); RepeatInfinite(1, Box::new(item)) },
+                Some(&'?') => { self.next(); Repeat(0..2, Box::new(item)) },
+                Some(&'{') => { self.next(); self.parse_counted_repeat(item)? },
+                _          => return Ok(item)
+            };
+        }
+    }
+
+    ///
+    /// Parses a number
+    ///
+    fn parse_number(&mut self) -> Option<u32> {
+        let mut result: Option<u32> = None;
+
+        while let Some(digit) = self.chars.peek().and_then(|chr| chr.to_digit(10)) {
+            self.next();
+            result = Some(result.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+        }
+
+        result
+    }
+
+    ///
+    /// Parses the remainder of a `{m,n}` repetition (after the opening '{')
+    ///
+    fn parse_counted_repeat(&mut self, item: Pattern<char>) -> Result<Pattern<char>, RegexParseError> {
+        let start_pos   = self.pos-1;
+        let min_count   = self.parse_number().ok_or(RegexParseError::InvalidRepetition(start_pos))?;
+
+        match self.expect_next()? {
+            '}' => {
+                let end_count = min_count.checked_add(1).ok_or(RegexParseError::InvalidRepetition(start_pos))?;
+                Ok(Repeat(min_count..end_count, Box::new(item)))
+            },
+
+            ',' => {
+                let max_count = self.parse_number();
+
+                if self.expect_next()? != '}' {
+                    return Err(RegexParseError::InvalidRepetition(start_pos));
+                }
+
+                match max_count {
+                    None                                        => Ok(RepeatInfinite(min_count, Box::new(item))),
+                    Some(max_count) if max_count >= min_count   => {
+                        let end_count = max_count.checked_add(1).ok_or(RegexParseError::InvalidRepetition(start_pos))?;
+                        Ok(Repeat(min_count..end_count, Box::new(item)))
+                    },
+                    Some(_)                                     => Err(RegexParseError::InvalidRepetition(start_pos))
+                }
+            },
+
+            _ => Err(RegexParseError::InvalidRepetition(start_pos))
+        }
+    }
+
+    ///
+    /// Parses a single item: a character, a group, a class or an escape
+    ///
+    fn parse_item(&mut self) -> Result<Pattern<char>, RegexParseError> {
+        let pos = self.pos;
+
+        match self.expect_next()? {
+            '(' => {
+                let group = self.parse_alternatives()?;
+
+                match self.expect_next()? {
+                    ')'     => Ok(group),
+                    other   => Err(RegexParseError::UnexpectedCharacter(self.pos-1, other))
+                }
+            },
+
+            '[' => self.parse_class(),
+
+            '.' => Ok(pattern_for_ranges(negate_ranges(vec![('\n', '\n')]))),
+
+            '\\' => {
+                let escaped = self.expect_next()?;
+
+                match class_escape(escaped) {
+                    Some(ranges)    => Ok(pattern_for_ranges(ranges)),
+                    None            => Ok(Match(vec![literal_escape(escaped)]))
+                }
+            },
+
+            chr @ '*' | chr @ '+' | chr @ '?' | chr @ '{' | chr @ ')' | chr @ '|' => Err(RegexParseError::UnexpectedCharacter(pos, chr)),
+
+            chr => Ok(Match(vec![chr]))
+        }
+    }
+
+    ///
+    /// Reads a single character from within a character class, returning None if it was a class escape such as `\d`
+    ///
+    fn parse_class_char(&mut self, ranges: &mut Vec<(char, char)>) -> Result<Option<char>, RegexParseError> {
+        match self.expect_next()? {
+            '\\' => {
+                let escaped = self.expect_next()?;
+
+                match class_escape(escaped) {
+                    Some(escape_ranges) => { ranges.extend(escape_ranges); Ok(None) },
+                    None                => Ok(Some(literal_escape(escaped)))
+                }
+            },
+
+            chr => Ok(Some(chr))
+        }
+    }
+
+    ///
+    /// Parses the remainder of a character class (after the opening '[')
+    ///
+    fn parse_class(&mut self) -> Result<Pattern<char>, RegexParseError> {
+        let mut ranges  = vec![];
+        let negated     = self.chars.peek() == Some(&'^');
+        let mut first   = true;
+
+        if negated { self.next(); }
+
+        loop {
+            // A ']' closes the class unless it's the first character
+            if self.chars.peek() == Some(&']') && !first {
+                self.next();
+                break;
+            }
+
+            first = false;
+
+            let start_pos   = self.pos;
+            let lowest      = self.parse_class_char(&mut ranges)?;
+
+            if let Some(lowest) = lowest {
+                // Might be the start of a range like 'a-z' (a '-' just before the closing ']' is literal)
+                if self.chars.peek() == Some(&'-') {
+                    self.next();
+
+                    if self.chars.peek() == Some(&']') {
+                        ranges.push((lowest, lowest));
+                        ranges.push(('-', '-'));
+                    } else {
+                        match self.parse_class_char(&mut ranges)? {
+                            Some(highest) if highest >= lowest  => ranges.push((lowest, highest)),
+                            _                                   => return Err(RegexParseError::InvalidClassRange(start_pos))
+                        }
+                    }
+                } else {
+                    ranges.push((lowest, lowest));
+                }
+            }
+        }
+
+        if negated {
+            ranges = negate_ranges(ranges);
+        }
+
+        Ok(pattern_for_ranges(ranges))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::matches::*;
+
+    #[test]
+    fn can_parse_literal() {
+        assert!(parse_regex("abc") == Ok(exactly("abc")));
+    }
+
+    #[test]
+    fn can_parse_repeated_group() {
+        let pattern = parse_regex("a(b|c)*d").unwrap();
+
+        assert!(matches("ad", pattern.clone()) == Some(2));
+        assert!(matches("abd", pattern.clone()) == Some(3));
+        assert!(matches("abcbcd", pattern.clone()) == Some(6));
+        assert!(matches("abxd", pattern.clone()) == None);
+        assert!(matches("abc", pattern.clone()) == None);
+    }
+
+    #[test]
+    fn can_parse_optional_and_one_or_more() {
+        let pattern = parse_regex("ab?c+").unwrap();
+
+        assert!(matches("ac", pattern.clone()) == Some(2));
+        assert!(matches("abccc", pattern.clone()) == Some(5));
+        assert!(matches("ab", pattern.clone()) == None);
+    }
+
+    #[test]
+    fn can_parse_counted_repeats() {
+        assert!(parse_regex("a{2}") == Ok(Repeat(2..3, Box::new(exactly("a")))));
+        assert!(parse_regex("a{2,}") == Ok(RepeatInfinite(2, Box::new(exactly("a")))));
+        assert!(parse_regex("a{2,4}") == Ok(Repeat(2..5, Box::new(exactly("a")))));
+
+        let pattern = parse_regex("x{2,3}").unwrap();
+        assert!(matches("x", pattern.clone()) == None);
+        assert!(matches("xxxx", pattern.clone()) == Some(3));
+    }
+
+    #[test]
+    fn can_parse_class() {
+        let pattern = parse_regex("[a-c_]+").unwrap();
+
+        assert!(matches("ab_cd", pattern.clone()) == Some(4));
+        assert!(matches("d", pattern.clone()) == None);
+    }
+
+    #[test]
+    fn can_parse_negated_class() {
+        let pattern = parse_regex("[^0-9]+").unwrap();
+
+        assert!(matches("ab1", pattern.clone()) == Some(2));
+        assert!(matches("1", pattern.clone()) == None);
+        assert!(matches("\u{1F600}x", pattern.clone()) == Some(2));
+    }
+
+    #[test]
+    fn can_parse_escapes() {
+        let pattern = parse_regex("\\d+\\.\\d+").unwrap();
+
+        assert!(matches("12.5", pattern.clone()) == Some(4));
+        assert!(matches("12x5", pattern.clone()) == None);
+    }
+
+    #[test]
+    fn dot_does_not_match_newline() {
+        let pattern = parse_regex("a.c").unwrap();
+
+        assert!(matches("abc", pattern.clone()) == Some(3));
+        assert!(matches("a\nc", pattern.clone()) == None);
+    }
+
+    #[test]
+    fn empty_alternative_matches_nothing() {
+        assert!(parse_regex("a|") == Ok(MatchAny(vec![exactly("a"), Epsilon])));
+    }
+
+    #[test]
+    fn unbalanced_parentheses_are_errors() {
+        assert!(parse_regex("(ab") == Err(RegexParseError::UnexpectedEndOfExpression));
+        assert!(parse_regex("ab)") == Err(RegexParseError::UnexpectedCharacter(2, ')')));
+    }
+
+    #[test]
+    fn nothing_to_repeat_is_error() {
+        assert!(parse_regex("*a") == Err(RegexParseError::UnexpectedCharacter(0, '*')));
+    }
+
+    #[test]
+    fn invalid_repeats_are_errors() {
+        assert!(parse_regex("a{3,2}") == Err(RegexParseError::InvalidRepetition(1)));
+        assert!(parse_regex("a{x}") == Err(RegexParseError::InvalidRepetition(1)));
+    }
+
+    #[test]
+    fn counted_repeat_at_maximum_count_is_an_error() {
+        assert!(parse_regex("a{4294967295}") == Err(RegexParseError::InvalidRepetition(1)));
+    }
+
+    #[test]
+    fn counted_repeat_range_at_maximum_count_is_an_error() {
+        assert!(parse_regex("a{1,4294967295}") == Err(RegexParseError::InvalidRepetition(1)));
+    }
+
+    #[test]
+    fn reversed_class_range_is_error() {
+        assert!(parse_regex("[z-a]") == Err(RegexParseError::InvalidClassRange(1)));
+    }
+}
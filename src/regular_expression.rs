@@ -19,12 +19,18 @@
 //!
 
 use super::regular_pattern::*;
+use super::regex_parse::*;
 
 impl Pattern<char> {
     ///
     /// Creates a new pattern from a regular expression
     ///
+    /// This will panic if the regular expression is not valid: use `parse_regex` to handle errors instead.
+    ///
     pub fn from_regex(pattern: &str) -> Pattern<char> {
-        unimplemented!()
+        match parse_regex(pattern) {
+            Ok(result)  => result,
+            Err(error)  => panic!("Invalid regular expression '{}': {}", pattern, error)
+        }
     }
 }
\ No newline at end of file
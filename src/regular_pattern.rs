@@ -50,16 +50,25 @@
 
 use std::iter::FromIterator;
 use std::ops::Range;
+use std::ops::RangeInclusive;
+use std::ops::Add;
+use std::ops::BitOr;
+use std::collections::HashSet;
+use std::fmt;
+use std::error::Error;
 
 use super::state_machine::*;
 use super::symbol_range::*;
 use super::ndfa::*;
 use super::countable::*;
+use super::overlapping_symbols::*;
+use super::prepare::*;
+use super::symbol_range_dfa::*;
 
 ///
 /// A Pattern represents a matching pattern in a regular language
 ///
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Pattern<Symbol: Clone> {
     ///
     /// Matches nothing
@@ -77,6 +86,8 @@ pub enum Pattern<Symbol: Clone> {
     /// Note that this is inclusive, so both the start and end symbols will be matched as well as any in between. Inclusive ranges allow
     /// the entire range of symbols to be matched (unlike exclusive ranges, which have to exclude at least one symbol by definition)
     ///
+    /// A range where the first symbol is after the last one is empty, and never matches anything.
+    ///
     MatchRange(Symbol, Symbol),
 
     ///
@@ -102,7 +113,7 @@ pub enum Pattern<Symbol: Clone> {
     MatchAny(Vec<Pattern<Symbol>>)
 }
 
-impl<Symbol: Clone+Ord+Countable> Pattern<Symbol> {
+impl<Symbol: Clone+Ord> Pattern<Symbol> {
     ///
     /// Compiles this pattern onto a state machine, returning the accepting symbol
     ///
@@ -127,7 +138,14 @@ impl<Symbol: Clone+Ord+Countable> Pattern<Symbol> {
 
             &MatchRange(ref first, ref last) => {
                 let next_state = state_machine.count_states();
-                state_machine.add_transition(start_state, SymbolRange::new(first.clone(), last.clone()), next_state);
+
+                if first <= last {
+                    state_machine.add_transition(start_state, SymbolRange::new(first.clone(), last.clone()), next_state);
+                } else {
+                    // A reversed range is empty, so this never matches: the end state is created but nothing can reach it
+                    state_machine.create_state(next_state);
+                }
+
                 next_state
             },
 
@@ -162,6 +180,10 @@ impl<Symbol: Clone+Ord+Countable> Pattern<Symbol> {
                 let target_state = state_machine.count_states();
                 state_machine.create_state(target_state);
 
+                // The copies of the pattern form a chain, with every copy after range.start sharing the same join to the
+                // target state. As the range is exclusive, only range.end-1 copies are needed: a copy after that could
+                // never reach the target. The DFA will still need a state for each symbol in the chain, as it needs to
+                // count the repetitions, but this keeps the NDFA to (range.end-1) * pattern states.
                 let mut repeat_state = start_state;
 
                 for repeat in 0..(range.end) {
@@ -170,8 +192,10 @@ impl<Symbol: Clone+Ord+Countable> Pattern<Symbol> {
                         state_machine.join_states(repeat_state, target_state)
                     }
 
-                    // Compile this iteration through the repetition
-                    repeat_state = pattern.compile(state_machine, repeat_state);
+                    // Compile the next iteration through the repetition, if there is one
+                    if repeat+1 < range.end {
+                        repeat_state = pattern.compile(state_machine, repeat_state);
+                    }
                 }
 
                 target_state
@@ -203,11 +227,259 @@ impl<Symbol: Clone+Ord+Countable> Pattern<Symbol> {
             }
         }
     }
+
+    ///
+    /// Returns the number of states that `compile` will add to a state machine for this pattern
+    ///
+    /// This can be used to reject patterns that would generate an excessively large state machine before compiling
+    /// them. The calculation saturates rather than overflowing for very large repetitions.
+    ///
+    pub fn estimated_states(&self) -> usize {
+        match self {
+            &Epsilon                            => 0,
+            &Match(ref symbols)                 => symbols.len(),
+            &MatchRange(_, _)                   => 1,
+            &RepeatInfinite(count, ref pattern) => (count as usize).saturating_add(2).saturating_mul(pattern.estimated_states()).saturating_add(1),
+            &Repeat(ref range, ref pattern)     => (range.end as usize).saturating_sub(1).saturating_mul(pattern.estimated_states()).saturating_add(1),
+            &MatchAll(ref patterns)             => patterns.iter().fold(0, |total, pattern| total.saturating_add(pattern.estimated_states())),
+            &MatchAny(ref patterns)             => patterns.iter().fold(1, |total, pattern| total.saturating_add(pattern.estimated_states()))
+        }
+    }
+
+    ///
+    /// Returns how deeply nested the patterns in this pattern are (a pattern with no subpatterns has a depth of 1)
+    ///
+    pub fn nesting_depth(&self) -> usize {
+        match self {
+            &Epsilon                        => 1,
+            &Match(_)                       => 1,
+            &MatchRange(_, _)               => 1,
+            &RepeatInfinite(_, ref pattern) => 1 + pattern.nesting_depth(),
+            &Repeat(_, ref pattern)         => 1 + pattern.nesting_depth(),
+            &MatchAll(ref patterns)         => 1 + patterns.iter().map(|pattern| pattern.nesting_depth()).max().unwrap_or(0),
+            &MatchAny(ref patterns)         => 1 + patterns.iter().map(|pattern| pattern.nesting_depth()).max().unwrap_or(0)
+        }
+    }
+
+    ///
+    /// If this pattern matches a single symbol from a range, returns that range
+    ///
+    fn single_symbol_range(&self) -> Option<SymbolRange<Symbol>> {
+        match self {
+            &MatchRange(ref lowest, ref highest) if lowest <= highest   => Some(SymbolRange::new(lowest.clone(), highest.clone())),
+            &Match(ref symbols) if symbols.len() == 1                   => Some(SymbolRange::new(symbols[0].clone(), symbols[0].clone())),
+            _                                                           => None
+        }
+    }
+
+    ///
+    /// Removes redundant alternatives from any `MatchAny` patterns in this pattern
+    ///
+    /// An alternative is redundant if it is identical to an earlier alternative, or if it matches a single symbol from a range
+    /// that is contained in the range of another alternative. A `MatchAny` left with a single alternative is replaced by that
+    /// alternative.
+    ///
+    pub fn dedupe_alternatives(&self) -> Pattern<Symbol> {
+        match self {
+            &Epsilon                                => Epsilon,
+            &Match(ref symbols)                     => Match(symbols.clone()),
+            &MatchRange(ref lowest, ref highest)    => MatchRange(lowest.clone(), highest.clone()),
+            &RepeatInfinite(count, ref pattern)     => RepeatInfinite(count, Box::new(pattern.dedupe_alternatives())),
+            &Repeat(ref range, ref pattern)         => Repeat(range.clone(), Box::new(pattern.dedupe_alternatives())),
+            &MatchAll(ref patterns)                 => MatchAll(patterns.iter().map(|pattern| pattern.dedupe_alternatives()).collect()),
+
+            &MatchAny(ref patterns) => {
+                // Remove any alternatives that are identical to an earlier one
+                let mut unique: Vec<Pattern<Symbol>> = vec![];
+
+                for pattern in patterns.iter().map(|pattern| pattern.dedupe_alternatives()) {
+                    if !unique.contains(&pattern) {
+                        unique.push(pattern);
+                    }
+                }
+
+                // Remove any alternatives whose range is contained by another alternative
+                let ranges: Vec<_>  = unique.iter().map(|pattern| pattern.single_symbol_range()).collect();
+                let mut result      = vec![];
+
+                for (index, pattern) in unique.into_iter().enumerate() {
+                    let is_contained = match ranges[index] {
+                        Some(ref range) => ranges.iter().enumerate().any(|(other_index, other_range)| {
+                            match other_range {
+                                &Some(ref other_range)  => other_index != index && other_range != range && other_range.contains_range(range),
+                                &None                   => false
+                            }
+                        }),
+                        None            => false
+                    };
+
+                    if !is_contained {
+                        result.push(pattern);
+                    }
+                }
+
+                if result.len() == 1 {
+                    result.pop().unwrap()
+                } else {
+                    MatchAny(result)
+                }
+            }
+        }
+    }
+
+    ///
+    /// Fuses adjacent `Match` patterns in any `MatchAll` patterns in this pattern into a single `Match`
+    ///
+    /// Patterns built up from several pieces can end up with sequences like `MatchAll([Match(a), Match(b)])`, which compile to
+    /// the same state machine as `Match(ab)` but are harder to read and compare. Nested `MatchAll` patterns are also flattened
+    /// so that their literals can be fused with their neighbours. A `MatchAll` left with a single pattern is replaced by that
+    /// pattern.
+    ///
+    pub fn optimize_literals(&self) -> Pattern<Symbol> {
+        match self {
+            &Epsilon                                => Epsilon,
+            &Match(ref symbols)                     => Match(symbols.clone()),
+            &MatchRange(ref lowest, ref highest)    => MatchRange(lowest.clone(), highest.clone()),
+            &RepeatInfinite(count, ref pattern)     => RepeatInfinite(count, Box::new(pattern.optimize_literals())),
+            &Repeat(ref range, ref pattern)         => Repeat(range.clone(), Box::new(pattern.optimize_literals())),
+            &MatchAny(ref patterns)                 => MatchAny(patterns.iter().map(|pattern| pattern.optimize_literals()).collect()),
+
+            &MatchAll(ref patterns) => {
+                // Flatten any nested MatchAll patterns
+                let mut flattened = vec![];
+
+                for pattern in patterns.iter().map(|pattern| pattern.optimize_literals()) {
+                    match pattern {
+                        MatchAll(nested)    => flattened.extend(nested),
+                        other               => flattened.push(other)
+                    }
+                }
+
+                // Fuse any adjacent literals
+                let mut result: Vec<Pattern<Symbol>> = vec![];
+
+                for pattern in flattened {
+                    if let (Some(&mut Match(ref mut previous)), &Match(ref symbols)) = (result.last_mut(), &pattern) {
+                        previous.extend(symbols.iter().cloned());
+                        continue;
+                    }
+
+                    result.push(pattern);
+                }
+
+                if result.len() == 1 {
+                    result.pop().unwrap()
+                } else {
+                    MatchAll(result)
+                }
+            }
+        }
+    }
+
+    ///
+    /// Collapses any `RepeatInfinite` patterns that directly contain another `RepeatInfinite` into a single repeat
+    ///
+    /// Repeating `x` at least `b` times, at least `a` times over, is the same as repeating `x` at least `a*b` times when `a`
+    /// is at least 1. So `(x+)+` becomes `x+`. When `b` is 0, every outer count gives `x*`, so `(x*)*` and `(x*)+` become `x*`.
+    /// `(x+)*` also becomes `x*`. Other nestings with an outer count of 0 can't be written as a single repeat, so they are
+    /// left as they are.
+    ///
+    pub fn flatten_repeats(&self) -> Pattern<Symbol> {
+        match self {
+            &Epsilon                                => Epsilon,
+            &Match(ref symbols)                     => Match(symbols.clone()),
+            &MatchRange(ref lowest, ref highest)    => MatchRange(lowest.clone(), highest.clone()),
+            &Repeat(ref range, ref pattern)         => Repeat(range.clone(), Box::new(pattern.flatten_repeats())),
+            &MatchAll(ref patterns)                 => MatchAll(patterns.iter().map(|pattern| pattern.flatten_repeats()).collect()),
+            &MatchAny(ref patterns)                 => MatchAny(patterns.iter().map(|pattern| pattern.flatten_repeats()).collect()),
+
+            &RepeatInfinite(outer_count, ref pattern) => {
+                match pattern.flatten_repeats() {
+                    RepeatInfinite(inner_count, inner_pattern) => {
+                        if inner_count == 0 || (outer_count == 0 && inner_count == 1) {
+                            RepeatInfinite(0, inner_pattern)
+                        } else if outer_count == 0 {
+                            RepeatInfinite(0, Box::new(RepeatInfinite(inner_count, inner_pattern)))
+                        } else if let Some(count) = outer_count.checked_mul(inner_count) {
+                            RepeatInfinite(count, inner_pattern)
+                        } else {
+                            RepeatInfinite(outer_count, Box::new(RepeatInfinite(inner_count, inner_pattern)))
+                        }
+                    },
+
+                    flattened => RepeatInfinite(outer_count, Box::new(flattened))
+                }
+            }
+        }
+    }
+}
+
+///
+/// A pattern that matches the strings matched by one pattern that are not matched by another
+///
+/// This is created by `Pattern::difference` and can be matched after calling `prepare_to_match()`. It can't be expressed as
+/// a pattern itself.
+///
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PatternDifference<Symbol: Clone> {
+    /// The strings that can be matched
+    pub pattern: Pattern<Symbol>,
+
+    /// The strings that are excluded from the match
+    pub excluding: Pattern<Symbol>
+}
+
+impl<Symbol: Clone> Pattern<Symbol> {
+    ///
+    /// Repeats this pattern for a certain number of iterations, returning an error if the range is empty or reversed
+    ///
+    /// This is the same as `repeat` except that ranges that contain no repetition counts (including `0..0`) are treated as an
+    /// error rather than producing a pattern that only matches the empty string or that never matches.
+    ///
+    pub fn try_repeat(self, count: Range<u32>) -> Result<Pattern<Symbol>, RepeatError> {
+        if count.start < count.end {
+            Ok(Repeat(count, Box::new(self)))
+        } else if count.start == count.end {
+            Err(RepeatError::EmptyRange(count))
+        } else {
+            Err(RepeatError::ReversedRange(count))
+        }
+    }
+
+    ///
+    /// Returns a pattern that matches the reverse of every string matched by this pattern
+    ///
+    pub fn reverse(&self) -> Pattern<Symbol> {
+        match self {
+            &Epsilon                                => Epsilon,
+            &MatchRange(ref lowest, ref highest)    => MatchRange(lowest.clone(), highest.clone()),
+            &RepeatInfinite(count, ref pattern)     => RepeatInfinite(count, Box::new(pattern.reverse())),
+            &Repeat(ref range, ref pattern)         => Repeat(range.clone(), Box::new(pattern.reverse())),
+            &MatchAny(ref patterns)                 => MatchAny(patterns.iter().map(|pattern| pattern.reverse()).collect()),
+            &MatchAll(ref patterns)                 => MatchAll(patterns.iter().rev().map(|pattern| pattern.reverse()).collect()),
+            &Match(ref symbols)                     => Match(symbols.iter().rev().cloned().collect())
+        }
+    }
+
+    ///
+    /// Creates a pattern that matches any string matched by this pattern, except for those matched by another pattern
+    ///
+    /// ```
+    /// # use concordance::*;
+    /// let not_abab = exactly("ab").repeat_forever(1).difference(exactly("abab"));
+    ///
+    /// assert!(matches("ababab", &not_abab) == Some(6));
+    /// assert!(matches("abab", &not_abab) == Some(2));
+    /// ```
+    ///
+    pub fn difference<Excluding: IntoPattern<Symbol>>(self, excluding: Excluding) -> PatternDifference<Symbol> {
+        PatternDifference { pattern: self, excluding: excluding.into_pattern() }
+    }
 }
 
 impl<Symbol: Clone+Ord+Countable+'static> ToNdfa<SymbolRange<Symbol>> for Pattern<Symbol> {
     fn to_ndfa<OutputSymbol: 'static>(&self, output: OutputSymbol) -> Box<StateMachine<SymbolRange<Symbol>, OutputSymbol>> {
-        let mut result  = Ndfa::new();
+        let mut result  = Ndfa::with_capacity(self.estimated_states().saturating_add(1));
         let end_state   = self.compile(&mut result, 0);
 
         result.set_output_symbol(end_state, output);
@@ -235,8 +507,124 @@ impl<Symbol: Clone+Ord+Countable+'static> ToNdfa<SymbolRange<Symbol>> for [Symbo
     }
 }
 
+///
+/// A list of patterns generates a state machine that accepts if any of the patterns match
+///
+impl<Symbol: Clone+Ord+Countable+'static> ToNdfa<SymbolRange<Symbol>> for [Pattern<Symbol>] {
+    fn to_ndfa<OutputSymbol: 'static>(&self, output: OutputSymbol) -> Box<StateMachine<SymbolRange<Symbol>, OutputSymbol>> {
+        let states      = self.iter().fold(2, |total: usize, pattern| total.saturating_add(pattern.estimated_states()));
+        let mut result  = Ndfa::with_capacity(states);
+
+        // Every pattern finishes on the same accepting state
+        let end_state   = result.count_states();
+        result.create_state(end_state);
+
+        for pattern in self {
+            // Compile each pattern starting at state 0
+            let pattern_end = pattern.compile(&mut result, 0);
+            result.join_states(pattern_end, end_state);
+        }
+
+        result.set_output_symbol(end_state, output);
+        result.fix_overlapping_ranges();
+
+        Box::new(result)
+    }
+}
+
 pub use Pattern::*;
 
+impl<Symbol: Clone+Ord+Countable> Pattern<Symbol> {
+    ///
+    /// Adds the ranges of symbols used by this pattern to a symbol map
+    ///
+    fn add_to_alphabet(&self, alphabet: &mut SymbolMap<Symbol>) {
+        match self {
+            &Epsilon                                => { },
+            &MatchRange(ref lowest, ref highest)    => if lowest <= highest { alphabet.add_range(&SymbolRange::new(lowest.clone(), highest.clone())) },
+            &RepeatInfinite(_, ref pattern)         => pattern.add_to_alphabet(alphabet),
+            &Repeat(_, ref pattern)                 => pattern.add_to_alphabet(alphabet),
+
+            &Match(ref symbols) => {
+                for symbol in symbols {
+                    alphabet.add_range(&SymbolRange::new(symbol.clone(), symbol.clone()));
+                }
+            },
+
+            &MatchAll(ref patterns) | &MatchAny(ref patterns) => {
+                for pattern in patterns {
+                    pattern.add_to_alphabet(alphabet);
+                }
+            }
+        }
+    }
+
+    ///
+    /// Returns the symbols used by this pattern, as a sorted list of non-overlapping ranges
+    ///
+    pub fn alphabet(&self) -> Vec<SymbolRange<Symbol>> {
+        let mut alphabet = SymbolMap::new();
+        self.add_to_alphabet(&mut alphabet);
+
+        alphabet.to_non_overlapping_map().ranges().to_vec()
+    }
+}
+
+impl<Symbol: Clone+Ord+Countable+'static> Pattern<Symbol> {
+    ///
+    /// Returns true if there is at least one string that is matched by both this pattern and another one
+    ///
+    /// Patterns that overlap will clash when added to the same tokenizer (only the lower output symbol will ever be
+    /// produced for the strings that they both match)
+    ///
+    pub fn overlaps(&self, other: &Pattern<Symbol>) -> bool {
+        let first: SymbolRangeDfa<Symbol, bool>     = self.to_ndfa(true).prepare_to_match();
+        let second: SymbolRangeDfa<Symbol, bool>    = other.to_ndfa(true).prepare_to_match();
+
+        // Search the product of the two DFAs for a state where both are accepting
+        let mut visited = HashSet::new();
+        let mut waiting = vec![(0, 0)];
+
+        visited.insert((0, 0));
+
+        while let Some((first_state, second_state)) = waiting.pop() {
+            if first.output_symbol_for_state(first_state).is_some() && second.output_symbol_for_state(second_state).is_some() {
+                return true;
+            }
+
+            let second_transitions = second.get_transitions_for_state(second_state);
+
+            for (first_range, first_target) in first.get_transitions_for_state(first_state) {
+                for &(ref second_range, second_target) in second_transitions.iter() {
+                    if first_range.overlaps(second_range) && visited.insert((first_target, second_target)) {
+                        waiting.push((first_target, second_target));
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    ///
+    /// Compiles this pattern into a DFA with the smallest possible number of states
+    ///
+    /// This performs every step needed to turn a pattern into a fast matcher: it's converted to an NDFA, overlapping ranges
+    /// are fixed, it's compiled into a DFA, and the result is minimized and has any unreachable states removed. Matching
+    /// states have an output symbol of `true`.
+    ///
+    /// `prepare_to_match()` skips the minimization step, so it's quicker when a pattern is only going to be used once or
+    /// twice, but this is usually the better choice for a pattern that's going to be matched many times.
+    ///
+    pub fn compile_to_dfa(&self) -> SymbolRangeDfa<Symbol, bool> {
+        let dfa: SymbolRangeDfa<Symbol, bool>   = self.to_ndfa(true).prepare_to_match();
+        let mut minimal                         = dfa.minimize();
+
+        minimal.remove_unreachable();
+        minimal
+    }
+}
+
 ///
 /// Implemented by things that can be converted into a pattern
 ///
@@ -292,6 +680,29 @@ impl<'a> IntoPattern<char> for &'a str {
     }
 }
 
+///
+/// An inclusive range converts to a pattern matching any symbol in that range. Ranges where the start is after the end
+/// are empty, so they produce a pattern that never matches.
+///
+impl<Symbol: Clone+Ord> From<RangeInclusive<Symbol>> for Pattern<Symbol> {
+    fn from(range: RangeInclusive<Symbol>) -> Pattern<Symbol> {
+        let (start, end) = range.into_inner();
+
+        if start <= end {
+            MatchRange(start, end)
+        } else {
+            MatchAny(vec![])
+        }
+    }
+}
+
+impl<Symbol: Clone+Ord> IntoPattern<Symbol> for RangeInclusive<Symbol> {
+    #[inline]
+    fn into_pattern(self) -> Pattern<Symbol> {
+        Pattern::from(self)
+    }
+}
+
 impl<Symbol: Clone> ToPattern<Symbol> for Pattern<Symbol> {
     #[inline]
     fn to_pattern(&self) -> Pattern<Symbol> {
@@ -336,8 +747,49 @@ pub trait PatternTransformer<Symbol: Clone> {
     /// Repeats the current pattern forever
     fn repeat_forever(self, min_count: u32) -> Pattern<Symbol>;
 
+    ///
     /// Repeats the current pattern for a certain number of iterations
+    ///
+    /// The range is exclusive, so `repeat(2..4)` matches the pattern two or three times. If the range is empty, `0..0`
+    /// produces `Epsilon` (the pattern is repeated no times), and any other empty or reversed range (such as `3..3` or `5..2`)
+    /// produces a pattern that never matches anything. Use `Pattern::try_repeat` to treat these ranges as an error instead.
+    ///
     fn repeat(self, count: Range<u32>) -> Pattern<Symbol>;
+
+    ///
+    /// Repeats the current pattern between `min` and `max` times, including `max`
+    ///
+    /// Unlike `repeat`, both ends of the range are inclusive, so this behaves like `{min,max}` in a regular expression:
+    /// `repeat_between(2, 3)` is the same as `repeat(2..4)`. If `max` is less than `min`, the pattern never matches
+    /// anything. `u32::MAX` can't be used as an inclusive upper bound, so it's treated as `u32::MAX-1`.
+    ///
+    fn repeat_between(self, min: u32, max: u32) -> Pattern<Symbol> where Self: Sized {
+        self.repeat(min..max.saturating_add(1))
+    }
+}
+
+///
+/// Error produced when a pattern is repeated with a range that contains no repetition counts
+///
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RepeatError {
+    /// The start and end of the range were the same (eg, `3..3`)
+    EmptyRange(Range<u32>),
+
+    /// The start of the range was after its end (eg, `5..2`)
+    ReversedRange(Range<u32>)
+}
+
+impl fmt::Display for RepeatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &RepeatError::EmptyRange(ref range)     => write!(f, "repeat range {}..{} is empty", range.start, range.end),
+            &RepeatError::ReversedRange(ref range)  => write!(f, "repeat range {}..{} is reversed", range.start, range.end)
+        }
+    }
+}
+
+impl Error for RepeatError {
 }
 
 ///
@@ -348,6 +800,89 @@ pub fn exactly<Symbol: Clone, PatternType: IntoPattern<Symbol>>(item: PatternTyp
     item.into_pattern()
 }
 
+///
+/// Creates a pattern that matches a string literally
+///
+/// This is the same as `exactly()` but always produces a pattern of characters, which avoids the need for type annotations
+/// in places where the symbol type would otherwise be ambiguous.
+///
+/// ```
+/// # use concordance::*;
+/// let keywords = vec![char_literal("if"), char_literal("else")];
+/// # assert!(keywords[0] == exactly("if"));
+/// ```
+///
+#[inline]
+pub fn char_literal(item: &str) -> Pattern<char> {
+    item.into_pattern()
+}
+
+///
+/// Creates a pattern that matches any single character that is not in the specified string
+///
+/// This is the equivalent of the regular expression character class `[^abc]`.
+///
+/// ```
+/// # use concordance::*;
+/// let not_quote = not_chars("\"");
+/// # assert!(matches("a", not_quote.clone()) == Some(1));
+/// # assert!(matches("\"", not_quote).is_none());
+/// ```
+///
+pub fn not_chars(excluded: &str) -> Pattern<char> {
+    let mut ranges = vec![SymbolRange::new('\u{0000}', char::MAX)];
+
+    for excluded_char in excluded.chars() {
+        let excluded_range = SymbolRange::new(excluded_char, excluded_char);
+        ranges = ranges.into_iter().flat_map(|range| range.subtract(&excluded_range)).collect();
+    }
+
+    MatchAny(ranges.into_iter().map(|range| MatchRange(range.lowest, range.highest)).collect())
+}
+
+///
+/// Creates a pattern that matches a sequence of bytes literally
+///
+/// Byte string literals such as `b"GET"` are fixed-length arrays, so this also saves having to convert them into slices
+/// before they can be used as patterns.
+///
+/// ```
+/// # use concordance::*;
+/// let get_or_put = byte_literal(b"GET").or(byte_literal(b"PUT"));
+/// # assert!(matches(&vec![b'P', b'U', b'T'], get_or_put).is_some());
+/// ```
+///
+#[inline]
+pub fn byte_literal(item: &[u8]) -> Pattern<u8> {
+    item.into_pattern()
+}
+
+///
+/// Creates a pattern that matches one or more items with a separator between each one
+///
+/// This is the same as `item (separator item)*` in a regular expression.
+///
+/// ```
+/// # use concordance::*;
+/// let numbers = separated(MatchRange('0', '9'), ",");
+/// # assert!(matches("1,2,3", numbers) == Some(5));
+/// ```
+///
+pub fn separated<Symbol: Clone, ItemPattern: IntoPattern<Symbol>, SeparatorPattern: IntoPattern<Symbol>>(item: ItemPattern, separator: SeparatorPattern) -> Pattern<Symbol> {
+    let item = item.into_pattern();
+
+    item.clone().append(separator.into_pattern().append(item).repeat_forever(0))
+}
+
+///
+/// Creates a pattern that matches zero or more items with a separator between each one
+///
+/// This is the same as `separated()` except that it also matches the empty string.
+///
+pub fn separated_opt<Symbol: Clone, ItemPattern: IntoPattern<Symbol>, SeparatorPattern: IntoPattern<Symbol>>(item: ItemPattern, separator: SeparatorPattern) -> Pattern<Symbol> {
+    separated(item, separator).or(Epsilon)
+}
+
 ///
 /// Implemented by things that combine patterns together to create new patterns
 ///
@@ -365,7 +900,15 @@ impl<Symbol: Clone> PatternTransformer<Symbol> for Pattern<Symbol> {
     }
 
     fn repeat(self, count: Range<u32>) -> Pattern<Symbol> {
-        Repeat(count, Box::new(self))
+        if count.start < count.end {
+            Repeat(count, Box::new(self))
+        } else if count.start == 0 && count.end == 0 {
+            // Repeating no times matches the empty string
+            Epsilon
+        } else {
+            // No number of repetitions is allowed, so nothing can match
+            MatchAny(vec![])
+        }
     }
 }
 
@@ -414,16 +957,513 @@ impl<Symbol: Clone, SecondPatternType: IntoPattern<Symbol>> PatternCombiner<Symb
             // Combining 'MatchAny(x)' and 'MatchAny(y)' should produce 'MatchAny(xy)'
             (MatchAny(first_string), MatchAny(second_string)) => MatchAny(first_string.into_iter().chain(second_string.into_iter()).collect()),
 
+            // Combining 'MatchAny(x)' and 'y' should produce 'MatchAny(xy)'
+            (MatchAny(first_string), second) => {
+                let mut result = first_string;
+                result.push(second);
+                MatchAny(result)
+            },
+
+            // Combining 'x' and 'MatchAny(y)' should produce 'MatchAny(xy)'
+            (first, MatchAny(second_string)) => {
+                let mut result = vec![first];
+                result.extend(second_string);
+                MatchAny(result)
+            },
+
             // Everything else is just MatchAny(xy)
             (first, second) => MatchAny(vec![first, second])
         }
     }
 }
 
+///
+/// `a + b` is the same as `a.append(b)`
+///
+impl<Symbol: Clone, SecondPatternType: IntoPattern<Symbol>> Add<SecondPatternType> for Pattern<Symbol> {
+    type Output = Pattern<Symbol>;
+
+    #[inline]
+    fn add(self, pattern: SecondPatternType) -> Pattern<Symbol> {
+        self.append(pattern)
+    }
+}
+
+///
+/// `a | b` is the same as `a.or(b)`
+///
+impl<Symbol: Clone, SecondPatternType: IntoPattern<Symbol>> BitOr<SecondPatternType> for Pattern<Symbol> {
+    type Output = Pattern<Symbol>;
+
+    #[inline]
+    fn bitor(self, pattern: SecondPatternType) -> Pattern<Symbol> {
+        self.or(pattern)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use super::super::state_machine::*;
+    use super::super::prepare::*;
+    use super::super::matches::*;
+    use super::super::pattern_matcher::*;
+    use super::super::symbol_reader::*;
+
+    #[test]
+    fn literal_overlaps_alternative() {
+        assert!(exactly("ab").overlaps(&exactly("ab").or("cd")));
+    }
+
+    #[test]
+    fn literal_does_not_overlap_different_literal() {
+        assert!(!exactly("ab").overlaps(&exactly("xy")));
+    }
+
+    #[test]
+    fn prefix_does_not_overlap() {
+        assert!(!exactly("ab").overlaps(&exactly("abc")));
+    }
+
+    #[test]
+    fn ranges_overlap() {
+        assert!(MatchRange('a', 'm').overlaps(&MatchRange('h', 'z')));
+        assert!(!MatchRange('a', 'g').overlaps(&MatchRange('h', 'z')));
+    }
+
+    #[test]
+    fn char_literal_is_same_as_exactly() {
+        let patterns = vec![char_literal("ab"), char_literal("cd")];
+
+        assert!(patterns[0] == exactly("ab"));
+        assert!(patterns[1] == Match(vec!['c', 'd']));
+    }
+
+    #[test]
+    fn byte_literal_from_byte_string() {
+        let pattern = byte_literal(b"abc").repeat_forever(1);
+
+        assert!(pattern == RepeatInfinite(1, Box::new(Match(vec![b'a', b'b', b'c']))));
+        assert!(matches(&vec![b'a', b'b', b'c', b'a', b'b', b'c'], pattern).is_some());
+    }
+
+    #[test]
+    fn estimated_states_for_bounded_repeat() {
+        let pattern = exactly("abc").repeat(0..10);
+
+        assert!(pattern.estimated_states() == 28);
+    }
+
+    #[test]
+    fn estimated_states_matches_compiled_states() {
+        let patterns = vec![
+            exactly("abc").repeat(2..5),
+            exactly("abc").or("de").repeat_forever(1),
+            MatchRange('a', 'z').append("xyz").or(Epsilon)
+        ];
+
+        for pattern in patterns {
+            let mut ndfa: Ndfa<SymbolRange<char>, ()> = Ndfa::new();
+            pattern.compile(&mut ndfa, 0);
+
+            assert!(ndfa.count_states() as usize == pattern.estimated_states() + 1);
+        }
+    }
+
+    #[test]
+    fn bounded_repeat_matches_every_length() {
+        // Ranges are exclusive, so this matches 'ab' between 0 and 50 times
+        let pattern = exactly("ab").repeat(0..51);
+        let dfa     = pattern.prepare_to_match();
+
+        for length in 0..103 {
+            let input: String   = "ab".chars().cycle().take(length).collect();
+            let expected        = length%2 == 0 && length <= 100;
+
+            assert!((matches_prepared(input.as_str(), &dfa) == Some(length)) == expected);
+        }
+    }
+
+    #[test]
+    fn bounded_repeat_does_not_compile_unreachable_copies() {
+        let pattern = exactly("ab").repeat(0..51);
+
+        let mut ndfa: Ndfa<SymbolRange<char>, ()> = Ndfa::new();
+        pattern.compile(&mut ndfa, 0);
+
+        // 50 copies of the two states for 'ab', plus the target and start states
+        assert!(ndfa.count_states() == 102);
+        assert!(pattern.prepare_to_match().count_states() <= 102);
+    }
+
+    #[test]
+    fn compile_to_dfa_matches_like_step_by_step_pipeline() {
+        let pattern     = exactly("abc").or("abd").repeat_forever(1).append(MatchRange('0', '9').repeat(0..3));
+        let compiled    = pattern.compile_to_dfa();
+        let prepared    = pattern.to_ndfa(true).prepare_to_match();
+
+        for input in vec!["abc", "abcabd", "abc12", "abc123", "ab", "abe", "", "abdabd9"] {
+            assert!(matches_prepared(input, &compiled) == matches_prepared(input, &prepared));
+        }
+    }
+
+    #[test]
+    fn compile_to_dfa_is_minimal() {
+        // a, then b or c, then d: 4 states however it's written
+        assert!(exactly("abd").or("acd").compile_to_dfa().count_states() == 4);
+        assert!(exactly("a").append(MatchRange('b', 'c')).append("d").compile_to_dfa().count_states() == 4);
+
+        // (ab)* needs just 2 states
+        assert!(exactly("ab").repeat_forever(0).compile_to_dfa().count_states() == 2);
+    }
+
+    #[test]
+    fn reversed_match_range_never_matches() {
+        let pattern = MatchRange('z', 'a');
+
+        assert!(matches("a", pattern.clone()) == None);
+        assert!(matches("z", pattern.clone()) == None);
+        assert!(pattern.compile_to_dfa().is_empty_language());
+        assert!(pattern.alphabet().is_empty());
+    }
+
+    #[test]
+    fn reversed_match_range_in_alternative() {
+        let pattern = MatchRange('z', 'a').or("abc");
+
+        assert!(matches("abc", pattern.clone()) == Some(3));
+        assert!(matches("m", pattern) == None);
+    }
+
+    #[test]
+    fn can_reverse_pattern() {
+        let pattern = exactly("ab").append(MatchRange('0', '9').repeat_forever(1)).append(exactly("cd").or("ef"));
+
+        assert!(pattern.reverse() == exactly("dc").or("fe").append(MatchRange('0', '9').repeat_forever(1)).append("ba"));
+        assert!(matches("fe12ba", pattern.reverse()) == Some(6));
+    }
+
+    #[test]
+    fn repeat_between_includes_upper_bound() {
+        let pattern = exactly("ab").repeat_between(2, 3);
+
+        assert!(pattern == exactly("ab").repeat(2..4));
+        assert!(matches("ab", pattern.clone()) == None);
+        assert!(matches("abab", pattern.clone()) == Some(4));
+        assert!(matches("ababab", pattern.clone()) == Some(6));
+        assert!(matches("abababab", pattern.clone()) == Some(6));
+    }
+
+    #[test]
+    fn repeat_between_same_bounds() {
+        assert!(matches("abab", exactly("ab").repeat_between(2, 2)) == Some(4));
+        assert!(matches("ab", exactly("ab").repeat_between(2, 2)) == None);
+        assert!(matches("ab", exactly("ab").repeat_between(3, 2)) == None);
+    }
+
+    #[test]
+    fn flatten_star_of_star() {
+        let nested = exactly("x").repeat_forever(0).repeat_forever(0);
+
+        assert!(nested.flatten_repeats() == exactly("x").repeat_forever(0));
+    }
+
+    #[test]
+    fn flatten_plus_of_plus() {
+        let nested = exactly("x").repeat_forever(1).repeat_forever(1);
+
+        assert!(nested.flatten_repeats() == exactly("x").repeat_forever(1));
+    }
+
+    #[test]
+    fn flatten_plus_of_star() {
+        let nested = exactly("x").repeat_forever(0).repeat_forever(1);
+
+        assert!(nested.flatten_repeats() == exactly("x").repeat_forever(0));
+    }
+
+    #[test]
+    fn flatten_star_of_plus() {
+        let nested = exactly("x").repeat_forever(1).repeat_forever(0);
+
+        assert!(nested.flatten_repeats() == exactly("x").repeat_forever(0));
+    }
+
+    #[test]
+    fn flattened_repeats_match_identically() {
+        for &(outer, inner) in &[(0, 0), (1, 1), (1, 0), (0, 1), (2, 3), (0, 2)] {
+            let nested      = exactly("ab").repeat_forever(inner).repeat_forever(outer).append("!");
+            let flattened   = nested.flatten_repeats();
+
+            for length in 0..10 {
+                let input = format!("{}!", "ab".repeat(length));
+
+                assert!(matches(input.as_str(), nested.clone()) == matches(input.as_str(), flattened.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn flatten_repeats_leaves_star_of_larger_repeat() {
+        let nested = exactly("x").repeat_forever(2).repeat_forever(0);
+
+        assert!(nested.flatten_repeats() == nested);
+    }
+
+    #[test]
+    fn not_chars_matches_other_characters() {
+        let pattern = not_chars("abc");
+
+        assert!(matches("d", pattern.clone()) == Some(1));
+        assert!(matches("X", pattern.clone()) == Some(1));
+        assert!(matches("1", pattern.clone()) == Some(1));
+        assert!(matches("a", pattern.clone()).is_none());
+        assert!(matches("b", pattern.clone()).is_none());
+        assert!(matches("c", pattern).is_none());
+    }
+
+    #[test]
+    fn not_chars_handles_surrogate_gap() {
+        let pattern = not_chars("\u{d7ff}\u{e000}");
+
+        assert!(matches("\u{d7fe}", pattern.clone()) == Some(1));
+        assert!(matches("\u{e001}", pattern.clone()) == Some(1));
+        assert!(matches("\u{10ffff}", pattern.clone()) == Some(1));
+        assert!(matches("\u{d7ff}", pattern.clone()).is_none());
+        assert!(matches("\u{e000}", pattern).is_none());
+    }
+
+    #[test]
+    fn separated_matches_items_between_separators() {
+        let digits = separated(MatchRange('0', '9'), ",");
+
+        assert!(matches("1,2,3", digits.clone()) == Some(5));
+        assert!(matches("1", digits.clone()) == Some(1));
+        assert!(matches("", digits.clone()) == None);
+
+        // The trailing separator isn't part of the match
+        assert!(matches("1,", digits.clone()) == Some(1));
+    }
+
+    #[test]
+    fn separated_opt_matches_empty_string() {
+        let digits = separated_opt(MatchRange('0', '9'), ",");
+
+        assert!(matches("", digits.clone()).is_some());
+        assert!(matches("1,2,3", digits) == Some(5));
+    }
+
+    #[test]
+    fn equal_patterns_hash_the_same() {
+        let mut patterns = HashSet::new();
+
+        patterns.insert(exactly("abc").repeat_forever(1));
+        patterns.insert(exactly("abc").repeat_forever(1));
+        patterns.insert(exactly("abc").repeat_forever(0));
+        patterns.insert(exactly("abc").or("def"));
+        patterns.insert(exactly("abc").or("def"));
+        patterns.insert(MatchRange('a', 'z'));
+
+        assert!(patterns.len() == 4);
+        assert!(patterns.contains(&exactly("abc").or("def")));
+        assert!(!patterns.contains(&exactly("def").or("abc")));
+    }
+
+    #[test]
+    fn estimated_states_saturates() {
+        let pattern = exactly("abc").repeat(0..u32::max_value()).repeat(0..u32::max_value()).repeat(0..u32::max_value());
+
+        assert!(pattern.estimated_states() == usize::max_value());
+    }
+
+    #[test]
+    fn nesting_depth_of_literal() {
+        assert!(exactly("abc").nesting_depth() == 1);
+    }
+
+    #[test]
+    fn nesting_depth_of_nested_alternation() {
+        let pattern = MatchAny(vec![
+            exactly("a"),
+            MatchAny(vec![
+                exactly("b"),
+                MatchAny(vec![exactly("c"), exactly("d")])
+            ])
+        ]);
+
+        assert!(pattern.nesting_depth() == 4);
+    }
+
+    #[test]
+    fn can_convert_inclusive_range_to_pattern() {
+        let pattern = ('0'..='9').into_pattern();
+
+        assert!(pattern == MatchRange('0', '9'));
+        assert!(matches("5", pattern.clone()) == Some(1));
+        assert!(matches("a", pattern.clone()) == None);
+        assert!(matches("123", pattern.repeat_forever(1)) == Some(3));
+    }
+
+    #[test]
+    fn reversed_inclusive_range_never_matches() {
+        let pattern = Pattern::from('9'..='0');
+
+        assert!(pattern == MatchAny(vec![]));
+        assert!(matches("5", pattern.clone()) == None);
+        assert!(matches("", pattern) == None);
+    }
+
+    #[test]
+    fn dedupe_removes_identical_alternatives() {
+        let pattern = MatchAny(vec![Match(vec!['a']), Match(vec!['a'])]);
+
+        assert!(pattern.dedupe_alternatives() == Match(vec!['a']));
+    }
+
+    #[test]
+    fn dedupe_removes_contained_range() {
+        let pattern = MatchAny(vec![MatchRange('c', 'f'), exactly("abc"), MatchRange('a', 'z')]);
+
+        assert!(pattern.dedupe_alternatives() == MatchAny(vec![exactly("abc"), MatchRange('a', 'z')]));
+    }
+
+    #[test]
+    fn dedupe_removes_symbol_within_range() {
+        let pattern = MatchAny(vec![MatchRange('a', 'z'), exactly("q")]);
+
+        assert!(pattern.dedupe_alternatives() == MatchRange('a', 'z'));
+    }
+
+    #[test]
+    fn dedupe_applies_to_nested_patterns() {
+        let pattern = exactly("ab").append(exactly("c").or("c")).repeat_forever(1);
+
+        assert!(pattern.dedupe_alternatives() == MatchAll(vec![exactly("ab"), exactly("c")]).repeat_forever(1));
+    }
+
+    #[test]
+    fn repeat_zero_times_is_epsilon() {
+        assert!(exactly("abc").repeat(0..0) == Epsilon);
+        assert!(matches("abc", exactly("abc").repeat(0..0)) == Some(0));
+    }
+
+    #[test]
+    fn repeat_empty_range_never_matches() {
+        assert!(exactly("abc").repeat(3..3) == MatchAny(vec![]));
+        assert!(matches("abcabcabc", exactly("abc").repeat(3..3)) == None);
+        assert!(matches("", exactly("abc").repeat(3..3)) == None);
+    }
+
+    #[test]
+    fn repeat_reversed_range_never_matches() {
+        let (start, end) = (5, 2);
+
+        assert!(exactly("abc").repeat(start..end) == MatchAny(vec![]));
+        assert!(matches("abc", exactly("abc").repeat(start..end)) == None);
+        assert!(matches("", exactly("abc").repeat(start..end)) == None);
+    }
+
+    #[test]
+    fn try_repeat_rejects_empty_ranges() {
+        let (start, end) = (5, 2);
+
+        assert!(exactly("abc").try_repeat(0..0) == Err(RepeatError::EmptyRange(0..0)));
+        assert!(exactly("abc").try_repeat(3..3) == Err(RepeatError::EmptyRange(3..3)));
+        assert!(exactly("abc").try_repeat(start..end) == Err(RepeatError::ReversedRange(start..end)));
+        assert!(exactly("abc").try_repeat(2..4) == Ok(exactly("abc").repeat(2..4)));
+    }
+
+    #[test]
+    fn or_flattens_alternatives_on_left() {
+        let pattern = exactly("a").or("b").or("c");
+
+        assert!(pattern == MatchAny(vec![exactly("a"), exactly("b"), exactly("c")]));
+    }
+
+    #[test]
+    fn or_flattens_alternatives_on_right() {
+        let pattern = exactly("a").or(exactly("b").or("c"));
+
+        assert!(pattern == MatchAny(vec![exactly("a"), exactly("b"), exactly("c")]));
+    }
+
+    #[test]
+    fn add_is_append() {
+        assert!(exactly("ab") + exactly("cd") == exactly("ab").append("cd"));
+        assert!(exactly("ab") + MatchRange('0', '9') + "cd" == exactly("ab").append(MatchRange('0', '9')).append("cd"));
+    }
+
+    #[test]
+    fn bitor_is_or() {
+        assert!(exactly("a") | exactly("b") == exactly("a").or("b"));
+        assert!(exactly("a") | "b" | "c" == exactly("a").or("b").or("c"));
+    }
+
+    #[test]
+    fn can_match_operator_patterns() {
+        let pattern = (exactly("a") | "b").repeat_forever(1) + "!";
+
+        assert!(matches("abba!", pattern.clone()) == Some(5));
+        assert!(matches("abc!", pattern) == None);
+    }
+
+    #[test]
+    fn optimize_literals_fuses_adjacent_matches() {
+        let pattern = MatchAll(vec![Match(vec!['a']), Match(vec!['b']), MatchRange('x', 'y'), Match(vec!['c'])]);
+
+        assert!(pattern.optimize_literals() == MatchAll(vec![Match(vec!['a', 'b']), MatchRange('x', 'y'), Match(vec!['c'])]));
+    }
+
+    #[test]
+    fn optimize_literals_flattens_nested_sequences() {
+        let pattern = MatchAll(vec![Match(vec!['a']), MatchAll(vec![Match(vec!['b']), Match(vec!['c'])])]).repeat_forever(1);
+
+        assert!(pattern.optimize_literals() == exactly("abc").repeat_forever(1));
+    }
+
+    #[test]
+    fn optimized_literals_match_same_strings() {
+        let pattern     = MatchAll(vec![Match(vec!['a']), Match(vec!['b']), MatchRange('x', 'y'), Match(vec!['c'])]);
+        let optimized   = pattern.optimize_literals();
+
+        for input in vec!["abxc", "abyc", "abzc", "ab", "abx"] {
+            assert!(matches(input, pattern.clone()) == matches(input, optimized.clone()));
+        }
+
+        assert!(optimized.estimated_states() <= pattern.estimated_states());
+    }
+
+    #[test]
+    fn alphabet_of_literal_and_range() {
+        let pattern = exactly("ab").or(MatchRange('x', 'z'));
+
+        assert!(pattern.alphabet() == vec![SymbolRange::new('a', 'a'), SymbolRange::new('b', 'b'), SymbolRange::new('x', 'z')]);
+    }
+
+    #[test]
+    fn alphabet_splits_overlapping_ranges() {
+        let pattern = MatchRange('a', 'm').append(exactly("c")).repeat_forever(1);
+
+        assert!(pattern.alphabet() == vec![SymbolRange::new('a', 'b'), SymbolRange::new('c', 'c'), SymbolRange::new('d', 'm')]);
+    }
+
+    #[test]
+    fn difference_matches_shorter_string() {
+        let pattern = exactly("ab").difference("abc");
+
+        assert!(matches("ab", &pattern) == Some(2));
+        assert!(matches("abc", &pattern) == Some(2));
+        assert!(matches("a", &pattern) == None);
+    }
+
+    #[test]
+    fn difference_excludes_longest_match() {
+        let pattern = exactly("ab").append(exactly("c").repeat(0..2)).difference("abc");
+
+        assert!(matches("abc", &pattern) == Some(2));
+        assert!(matches("ab", pattern.pattern.clone()) == Some(2));
+        assert!(matches("abc", pattern.pattern.clone()) == Some(3));
+    }
 
     #[test]
     fn can_convert_vec_to_pattern() {
@@ -529,4 +1569,25 @@ mod test {
         let ndfa_vec = vec.to_ndfa("success");
         assert!(ndfa_vec.count_states() > 1);
     }
+
+    #[test]
+    fn can_build_ndfa_from_pattern_list() {
+        let patterns = [exactly("ab"), exactly("cd")];
+        let dfa      = patterns.to_ndfa(true).prepare_to_match();
+
+        assert!(match_pattern(dfa.start(), &mut "ab".read_symbols()).is_accepted(&true));
+        assert!(match_pattern(dfa.start(), &mut "cd".read_symbols()).is_accepted(&true));
+        assert!(matches_prepared("ab", &dfa) == Some(2));
+        assert!(matches_prepared("cd", &dfa) == Some(2));
+        assert!(matches_prepared("ad", &dfa) == None);
+    }
+
+    #[test]
+    fn can_build_ndfa_from_pattern_vec() {
+        let patterns = vec![exactly("ab"), exactly("abc")];
+        let dfa      = patterns.to_ndfa(true).prepare_to_match();
+
+        assert!(matches_prepared("ab", &dfa) == Some(2));
+        assert!(matches_prepared("abcd", &dfa) == Some(3));
+    }
 }
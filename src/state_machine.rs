@@ -23,6 +23,7 @@
 //!
 
 use std::rc::*;
+use std::collections::HashMap;
 
 ///
 /// Identifies a state in a state machine
@@ -53,6 +54,51 @@ pub trait StateMachine<InputSymbol, OutputSymbol> {
     /// If a state is an accepting state, then this returns the output symbol that should be produced if this is the longest match
     ///
     fn output_symbol_for_state(&self, state: StateId) -> Option<&OutputSymbol>;
+
+    ///
+    /// Returns the state that a state moves to when none of the transitions from `get_transitions_for_state()` match
+    ///
+    /// Most state machines don't have default transitions, so the default implementation returns `None`.
+    ///
+    fn default_transition_for_state(&self, _state: StateId) -> Option<StateId> {
+        None
+    }
+
+    ///
+    /// Returns the state that a particular state moves to on seeing a symbol, or `None` if there is no transition for that symbol
+    ///
+    /// The default implementation searches the result of `get_transitions_for_state()` for a transition with an equal
+    /// symbol, and returns the first one it finds. If there isn't one, the state's default transition is used.
+    ///
+    fn transition_target(&self, state: StateId, symbol: &InputSymbol) -> Option<StateId>
+    where InputSymbol: PartialEq {
+        self.get_transitions_for_state(state).into_iter()
+            .find(|&(ref transition_symbol, _)| transition_symbol == symbol)
+            .map(|(_, target_state)| target_state)
+            .or_else(|| self.default_transition_for_state(state))
+    }
+
+    ///
+    /// Returns a map of the transitions for every state in this state machine
+    ///
+    /// Only the transitions returned by `get_transitions_for_state()` are included: default transitions can be found with
+    /// `default_transition_for_state()`.
+    ///
+    fn to_transition_map(&self) -> HashMap<StateId, Vec<(InputSymbol, StateId)>> {
+        (0..self.count_states())
+            .map(|state| (state, self.get_transitions_for_state(state)))
+            .collect()
+    }
+
+    ///
+    /// Returns a map of the output symbols for every accepting state in this state machine
+    ///
+    fn accepting_states(&self) -> HashMap<StateId, OutputSymbol> 
+    where OutputSymbol: Clone {
+        (0..self.count_states())
+            .filter_map(|state| self.output_symbol_for_state(state).map(|output| (state, output.clone())))
+            .collect()
+    }
 }
 
 ///
@@ -117,6 +163,17 @@ impl<InputSymbol, OutputSymbol> StateMachine<InputSymbol, OutputSymbol> for Rc<S
     fn output_symbol_for_state(&self, state: StateId) -> Option<&OutputSymbol> {
         (**self).output_symbol_for_state(state)
     }
+
+    #[inline]
+    fn default_transition_for_state(&self, state: StateId) -> Option<StateId> {
+        (**self).default_transition_for_state(state)
+    }
+
+    #[inline]
+    fn transition_target(&self, state: StateId, symbol: &InputSymbol) -> Option<StateId>
+    where InputSymbol: PartialEq {
+        (**self).transition_target(state, symbol)
+    }
 }
 
 ///
@@ -137,4 +194,15 @@ impl<'a, InputSymbol, OutputSymbol> StateMachine<InputSymbol, OutputSymbol> for
     fn output_symbol_for_state(&self, state: StateId) -> Option<&OutputSymbol> {
         (**self).output_symbol_for_state(state)
     }
+
+    #[inline]
+    fn default_transition_for_state(&self, state: StateId) -> Option<StateId> {
+        (**self).default_transition_for_state(state)
+    }
+
+    #[inline]
+    fn transition_target(&self, state: StateId, symbol: &InputSymbol) -> Option<StateId>
+    where InputSymbol: PartialEq {
+        (**self).transition_target(state, symbol)
+    }
 }
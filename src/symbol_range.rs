@@ -26,6 +26,12 @@
 //!
 
 use std::cmp::*;
+use std::fmt;
+use std::error::Error;
+use std::convert::TryFrom;
+use std::ops::{Range, RangeInclusive, RangeBounds, Bound};
+
+use super::countable::*;
 
 ///
 /// Represents a range of symbols
@@ -99,6 +105,30 @@ impl<Symbol: Ord> SymbolRange<Symbol> {
     pub fn includes(&self, symbol: &Symbol) -> bool {
         self.lowest <= *symbol && *symbol <= self.highest
     }
+
+    ///
+    /// True if every symbol in another range is also in this range
+    ///
+    #[inline]
+    pub fn contains_range(&self, other: &SymbolRange<Symbol>) -> bool {
+        self.lowest <= other.lowest && other.highest <= self.highest
+    }
+
+    ///
+    /// True if this range contains exactly one symbol
+    ///
+    #[inline]
+    pub fn is_single(&self) -> bool {
+        self.lowest == self.highest
+    }
+
+    ///
+    /// True if this range contains exactly one symbol, and that symbol is the specified one
+    ///
+    #[inline]
+    pub fn eq_symbol(&self, symbol: &Symbol) -> bool {
+        self.is_single() && self.lowest == *symbol
+    }
 }
 
 impl<Symbol: Ord+Clone> SymbolRange<Symbol> {
@@ -114,11 +144,145 @@ impl<Symbol: Ord+Clone> SymbolRange<Symbol> {
             highest: if with.highest<self.highest { self.highest.clone() } else { with.highest.clone() }
         }
     }
+
+    ///
+    /// Restricts this range so that it only covers symbols that are within some bounds
+    ///
+    /// This returns the symbols that are in both this range and the bounds, or `None` if the two ranges don't overlap.
+    ///
+    pub fn clamp_to(&self, bounds: &SymbolRange<Symbol>) -> Option<SymbolRange<Symbol>> {
+        if self.overlaps(bounds) {
+            Some(SymbolRange {
+                lowest:  if bounds.lowest>self.lowest   { bounds.lowest.clone()  } else { self.lowest.clone()  },
+                highest: if bounds.highest<self.highest { bounds.highest.clone() } else { self.highest.clone() }
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<Symbol: Ord+Clone+Countable> SymbolRange<Symbol> {
+    ///
+    /// Returns the ranges covering the symbols that are in this range but not in another
+    ///
+    /// The result is empty if the other range covers all of this one, contains two ranges if the other range is strictly
+    /// inside this one, and otherwise contains a single range.
+    ///
+    pub fn subtract(&self, other: &SymbolRange<Symbol>) -> Vec<SymbolRange<Symbol>> {
+        if !self.overlaps(other) {
+            return vec![self.clone()];
+        }
+
+        let mut result = vec![];
+
+        if self.lowest < other.lowest {
+            result.push(SymbolRange { lowest: self.lowest.clone(), highest: other.lowest.prev() });
+        }
+
+        if other.highest < self.highest {
+            result.push(SymbolRange { lowest: other.highest.next(), highest: self.highest.clone() });
+        }
+
+        result
+    }
+}
+
+///
+/// An inclusive range converts directly to a symbol range (this will panic if the range is reversed, in the same way as
+/// `SymbolRange::new()`)
+///
+impl<Symbol: Ord> From<RangeInclusive<Symbol>> for SymbolRange<Symbol> {
+    fn from(range: RangeInclusive<Symbol>) -> SymbolRange<Symbol> {
+        let (lowest, highest) = range.into_inner();
+
+        SymbolRange::new(lowest, highest)
+    }
+}
+
+///
+/// Error produced when converting an exclusive range that contains no symbols into a symbol range
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EmptyRangeError;
+
+impl fmt::Display for EmptyRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "range contains no symbols")
+    }
+}
+
+impl Error for EmptyRangeError {
+}
+
+///
+/// Exclusive ranges can be converted to symbol ranges for countable symbols, where the highest symbol is the one before the
+/// end of the range. A symbol range always contains at least one symbol, so empty ranges can't be converted.
+///
+impl<Symbol: Ord+Countable> TryFrom<Range<Symbol>> for SymbolRange<Symbol> {
+    type Error = EmptyRangeError;
+
+    fn try_from(range: Range<Symbol>) -> Result<SymbolRange<Symbol>, EmptyRangeError> {
+        if range.start < range.end {
+            let highest = range.end.prev();
+            Ok(SymbolRange { lowest: range.start, highest: highest })
+        } else {
+            Err(EmptyRangeError)
+        }
+    }
+}
+
+impl<Symbol: Ord> RangeBounds<Symbol> for SymbolRange<Symbol> {
+    #[inline]
+    fn start_bound(&self) -> Bound<&Symbol> {
+        Bound::Included(&self.lowest)
+    }
+
+    #[inline]
+    fn end_bound(&self) -> Bound<&Symbol> {
+        Bound::Included(&self.highest)
+    }
+}
+
+///
+/// Writes a character so it can be read as part of a character class
+///
+/// Control characters are written as escape sequences, as are the characters that have a special meaning in a character
+/// class.
+///
+fn write_class_char(f: &mut fmt::Formatter, c: char) -> fmt::Result {
+    match c {
+        '\t'                                => write!(f, "\\t"),
+        '\n'                                => write!(f, "\\n"),
+        '\r'                                => write!(f, "\\r"),
+        '\\' | '[' | ']' | '-' | '^'        => write!(f, "\\{}", c),
+        c if c.is_control()                 => write!(f, "{}", c.escape_unicode()),
+        c                                   => write!(f, "{}", c)
+    }
+}
+
+///
+/// Character ranges are written like regular expression character classes: `[a-z]`, or just `a` for a range containing
+/// a single character
+///
+impl fmt::Display for SymbolRange<char> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_single() {
+            write_class_char(f, self.lowest)
+        } else {
+            write!(f, "[")?;
+            write_class_char(f, self.lowest)?;
+            write!(f, "-")?;
+            write_class_char(f, self.highest)?;
+            write!(f, "]")
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::collections::BTreeSet;
 
     #[test]
     fn can_create_range() {
@@ -227,4 +391,122 @@ mod test {
         let just_zero = SymbolRange::new(1,4);
         assert!(!just_zero.includes(&5));        
     }
+
+    #[test]
+    fn contains_range_within() {
+        assert!(SymbolRange::new(1, 10).contains_range(&SymbolRange::new(3, 5)));
+    }
+
+    #[test]
+    fn does_not_contain_overlapping_range() {
+        assert!(!SymbolRange::new(1, 5).contains_range(&SymbolRange::new(3, 7)));
+    }
+
+    #[test]
+    fn does_not_contain_larger_range() {
+        assert!(!SymbolRange::new(3, 5).contains_range(&SymbolRange::new(1, 10)));
+    }
+
+    #[test]
+    fn contains_same_range() {
+        assert!(SymbolRange::new(1, 5).contains_range(&SymbolRange::new(1, 5)));
+    }
+
+    #[test]
+    fn can_clamp_range_to_bounds() {
+        assert!(SymbolRange::new(0, 100).clamp_to(&SymbolRange::new(10, 20)) == Some(SymbolRange::new(10, 20)));
+        assert!(SymbolRange::new(15, 100).clamp_to(&SymbolRange::new(10, 20)) == Some(SymbolRange::new(15, 20)));
+        assert!(SymbolRange::new(0, 100).clamp_to(&SymbolRange::new(100, 300)) == Some(SymbolRange::new(100, 100)));
+    }
+
+    #[test]
+    fn clamp_to_disjoint_bounds_is_none() {
+        assert!(SymbolRange::new(0, 100).clamp_to(&SymbolRange::new(200, 300)) == None);
+    }
+
+    #[test]
+    fn subtract_from_middle_splits_range() {
+        assert!(SymbolRange::new(0, 10).subtract(&SymbolRange::new(4, 6)) == vec![SymbolRange::new(0, 3), SymbolRange::new(7, 10)]);
+    }
+
+    #[test]
+    fn subtract_from_ends() {
+        assert!(SymbolRange::new(0, 10).subtract(&SymbolRange::new(0, 6)) == vec![SymbolRange::new(7, 10)]);
+        assert!(SymbolRange::new(0, 10).subtract(&SymbolRange::new(4, 12)) == vec![SymbolRange::new(0, 3)]);
+    }
+
+    #[test]
+    fn subtract_non_overlapping_or_covering_range() {
+        assert!(SymbolRange::new(0, 10).subtract(&SymbolRange::new(11, 12)) == vec![SymbolRange::new(0, 10)]);
+        assert!(SymbolRange::new(4, 6).subtract(&SymbolRange::new(0, 10)) == vec![]);
+    }
+
+    #[test]
+    fn single_symbol_range_equals_symbol() {
+        let range = SymbolRange::new('a', 'a');
+
+        assert!(range.is_single());
+        assert!(range.eq_symbol(&'a'));
+        assert!(!range.eq_symbol(&'b'));
+    }
+
+    #[test]
+    fn multiple_symbol_range_does_not_equal_symbol() {
+        let range = SymbolRange::new('a', 'c');
+
+        assert!(!range.is_single());
+        assert!(!range.eq_symbol(&'a'));
+        assert!(!range.eq_symbol(&'b'));
+        assert!(!range.eq_symbol(&'c'));
+    }
+
+    #[test]
+    fn can_convert_from_inclusive_range() {
+        let range: SymbolRange<char> = ('a'..='z').into();
+
+        assert!(range == SymbolRange::new('a', 'z'));
+    }
+
+    #[test]
+    fn can_convert_from_exclusive_range() {
+        assert!(SymbolRange::try_from(0u32..10) == Ok(SymbolRange::new(0, 9)));
+        assert!(SymbolRange::try_from(5u32..6) == Ok(SymbolRange::new(5, 5)));
+    }
+
+    #[test]
+    fn empty_exclusive_range_does_not_convert() {
+        let (start, end) = (7u32, 5u32);
+
+        assert!(SymbolRange::try_from(5u32..5) == Err(EmptyRangeError));
+        assert!(SymbolRange::try_from(start..end) == Err(EmptyRangeError));
+    }
+
+    #[test]
+    fn symbol_range_is_range_bounds() {
+        let range = SymbolRange::new(3, 5);
+
+        assert!(range.contains(&3));
+        assert!(range.contains(&5));
+        assert!(!range.contains(&6));
+
+        let set: BTreeSet<u32> = (0..8).collect();
+        assert!(set.range(range).cloned().collect::<Vec<_>>() == vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn display_char_range_as_class() {
+        assert!(SymbolRange::new('a', 'z').to_string() == "[a-z]");
+    }
+
+    #[test]
+    fn display_single_char_range() {
+        assert!(SymbolRange::new('a', 'a').to_string() == "a");
+    }
+
+    #[test]
+    fn display_escapes_control_characters() {
+        assert!(SymbolRange::new('\t', '\n').to_string() == "[\\t-\\n]");
+        assert!(SymbolRange::new('-', ']').to_string() == "[\\--\\]]");
+        assert!(SymbolRange::new('\u{1}', '\u{1}').to_string() == "\\u{1}");
+    }
 }
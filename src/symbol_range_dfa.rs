@@ -19,12 +19,19 @@
 //!
 
 use std::mem::*;
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::VecDeque;
 
 use super::countable::*;
 use super::dfa_builder::*;
 use super::pattern_matcher::*;
 use super::symbol_range::*;
 use super::state_machine::*;
+use super::symbol_reader::*;
+use super::overlapping_symbols::*;
 
 ///
 /// DFA that decides on transitions based on non-overlapping, sorted lists of input symbols
@@ -44,7 +51,29 @@ pub struct SymbolRangeDfa<InputSymbol: Ord, OutputSymbol> {
     //
     // The accepting symbol for each state
     //
-    accept: Vec<Option<OutputSymbol>>
+    accept: Vec<Option<OutputSymbol>>,
+
+    //
+    // The state to move to for each state when none of its transitions match the input symbol (DFAs serialized before this
+    // was added have no entries here, and so have no default transitions)
+    //
+    #[serde(default)]
+    default_transitions: Vec<Option<StateId>>
+}
+
+///
+/// DFAs are equal if they have the same states, transitions and output symbols
+///
+/// A state with no entry in the default transitions list has no default transition, so a missing entry is considered to
+/// be the same as an entry of `None` (DFAs from the builder have an entry for every state, but deserialized DFAs may not).
+///
+impl<InputSymbol: Ord, OutputSymbol: PartialEq> PartialEq for SymbolRangeDfa<InputSymbol, OutputSymbol> {
+    fn eq(&self, other: &SymbolRangeDfa<InputSymbol, OutputSymbol>) -> bool {
+        self.states == other.states
+            && self.transitions == other.transitions
+            && self.accept == other.accept
+            && (0..self.accept.len()).all(|state| self.default_transition_for_state(state as StateId) == other.default_transition_for_state(state as StateId))
+    }
 }
 
 ///
@@ -53,12 +82,24 @@ pub struct SymbolRangeDfa<InputSymbol: Ord, OutputSymbol> {
 pub struct SymbolRangeDfaBuilder<InputSymbol: Ord+Countable, OutputSymbol> {
     states: Vec<usize>,
     transitions: Vec<(SymbolRange<InputSymbol>, StateId)>,
-    accept: Vec<Option<OutputSymbol>>
+    accept: Vec<Option<OutputSymbol>>,
+    default_transitions: Vec<Option<StateId>>
 }
 
 impl<InputSymbol: Ord+Countable, OutputSymbol> SymbolRangeDfaBuilder<InputSymbol, OutputSymbol> {
     pub fn new() -> SymbolRangeDfaBuilder<InputSymbol, OutputSymbol> {
-        SymbolRangeDfaBuilder { states: vec![], transitions: vec![], accept: vec![] }
+        SymbolRangeDfaBuilder { states: vec![], transitions: vec![], accept: vec![], default_transitions: vec![] }
+    }
+
+    ///
+    /// Sets the state that the current state moves to when none of its transitions match the input symbol
+    ///
+    /// Without a default transition, the DFA stops when it reads a symbol that doesn't match any transition. This makes it
+    /// possible to represent a transition for 'any other symbol' without having to list every range that's left over.
+    ///
+    pub fn default_transition(&mut self, target_state: StateId) {
+        self.default_transitions.pop();
+        self.default_transitions.push(Some(target_state));
     }
 }
 
@@ -87,6 +128,7 @@ impl<InputSymbol: Ord+Countable+Clone, OutputSymbol> DfaBuilder<SymbolRange<Inpu
         // Begin the next state
         self.states.push(self.transitions.len());
         self.accept.push(None);
+        self.default_transitions.push(None);
     }
 
     fn transition(&mut self, symbol: SymbolRange<InputSymbol>, target_state: StateId) {
@@ -100,7 +142,7 @@ impl<InputSymbol: Ord+Countable+Clone, OutputSymbol> DfaBuilder<SymbolRange<Inpu
 
     fn build(self) -> SymbolRangeDfa<InputSymbol, OutputSymbol> {
         // Turn into a RangeDfa
-        let mut result = SymbolRangeDfa { states: self.states, transitions: self.transitions, accept: self.accept };
+        let mut result = SymbolRangeDfa { states: self.states, transitions: self.transitions, accept: self.accept, default_transitions: self.default_transitions };
 
         // 'Cap' the last state so we don't need to special-case it later 
         // ie, we can always find the index of the last symbol by looking at the next state and don't need to handle the final state differently
@@ -118,7 +160,7 @@ impl<InputSymbol: Ord+Clone, OutputSymbol> StateMachine<SymbolRange<InputSymbol>
     /// first unused state in this state machine.
     ///
     fn count_states(&self) -> StateId {
-        (self.states.len()-1) as StateId
+        self.states.len().saturating_sub(1) as StateId
     }
 
     ///
@@ -145,6 +187,46 @@ impl<InputSymbol: Ord+Clone, OutputSymbol> StateMachine<SymbolRange<InputSymbol>
     fn output_symbol_for_state(&self, state: StateId) -> Option<&OutputSymbol> {
         self.accept[state as usize].as_ref()
     }
+
+    ///
+    /// Returns the state that a state moves to when none of its transitions match the input symbol, if it has one
+    ///
+    #[inline]
+    fn default_transition_for_state(&self, state: StateId) -> Option<StateId> {
+        SymbolRangeDfa::default_transition_for_state(self, state)
+    }
+
+    ///
+    /// Returns the state that a particular state moves to on seeing a range of symbols
+    ///
+    /// This finds the transition whose range contains all of the symbols in the specified range by binary searching the
+    /// (sorted, non-overlapping) transitions for the state, so it doesn't need to allocate. If the range doesn't overlap any
+    /// of the transitions, the default transition for the state is used.
+    ///
+    fn transition_target(&self, state: StateId, symbol: &SymbolRange<InputSymbol>) -> Option<StateId>
+    where SymbolRange<InputSymbol>: PartialEq {
+        let start_index = self.states[state as usize];
+        let end_index   = self.states[(state+1) as usize];
+        let transitions = &self.transitions[start_index..end_index];
+
+        let found = transitions.binary_search_by(|&(ref range, _)| {
+            if range.highest < symbol.lowest {
+                Ordering::Less
+            } else if range.lowest > symbol.highest {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        });
+
+        match found {
+            Ok(index)   => {
+                let (ref range, target_state) = transitions[index];
+                if range.contains_range(symbol) { Some(target_state) } else { None }
+            },
+            Err(_)      => SymbolRangeDfa::default_transition_for_state(self, state)
+        }
+    }
 }
 
 ///
@@ -166,6 +248,14 @@ pub struct SymbolRangeState<'a, InputSymbol: Ord+'a, OutputSymbol: 'a> {
 }
 
 impl<InputSymbol: Ord, OutputSymbol> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+    ///
+    /// Returns the state that a state moves to when none of its transitions match, if it has one
+    ///
+    #[inline]
+    pub fn default_transition_for_state(&self, state: StateId) -> Option<StateId> {
+        self.default_transitions.get(state as usize).and_then(|target_state| *target_state)
+    }
+
     ///
     /// Returns a `MatchAction` for the initial state of the DFA
     ///
@@ -185,126 +275,1476 @@ impl<InputSymbol: Ord, OutputSymbol> SymbolRangeDfa<InputSymbol, OutputSymbol> {
         let state_size          = size_of::<usize>() * self.states.len();
         let transitions_size    = size_of::<(SymbolRange<InputSymbol>, StateId)>() * self.transitions.len();
         let accept_size         = size_of::<Option<OutputSymbol>>() * self.accept.len();
-        let total_size          = state_size + transitions_size + accept_size;
+        let default_size        = size_of::<Option<StateId>>() * self.default_transitions.len();
+        let total_size          = state_size + transitions_size + accept_size + default_size;
 
         format!("SymbolRangeDfa: {} states, {} total transitions. {} bytes", self.states.len(), self.transitions.len(), total_size)
     }
-}
 
-impl<'a, InputSymbol: Ord+'a, OutputSymbol: 'a> MatchingState<'a, InputSymbol, OutputSymbol> for SymbolRangeState<'a, InputSymbol, OutputSymbol> {
-    fn next(self, symbol: InputSymbol) -> MatchAction<'a, OutputSymbol, Self> {
-        // The transition range is defined by the current state
-        let start_transition    = self.state_machine.states[self.state as usize];
-        let end_transition      = self.state_machine.states[self.state as usize+1];
+    ///
+    /// Returns the total number of transitions in this DFA (not counting default transitions)
+    ///
+    pub fn count_transitions(&self) -> usize {
+        self.transitions.len()
+    }
 
-        // See if there is an input symbol matching this transition
-        // TODO: consider binary searching for states with large numbers of transitions? (Do these occur regularly in patterns that people use?)
-        for transit in start_transition..end_transition {
-            // Test this transition
-            let (ref range, new_state) = self.state_machine.transitions[transit];
+    ///
+    /// Returns an estimate of the number of bytes of heap memory used by this DFA
+    ///
+    /// This is based on the capacity of the vectors used to store the DFA, so it includes space that has been allocated
+    /// but not used. Any heap memory owned by the input or output symbols themselves is not included.
+    ///
+    pub fn memory_footprint(&self) -> usize {
+        let state_size          = size_of::<usize>() * self.states.capacity();
+        let transitions_size    = size_of::<(SymbolRange<InputSymbol>, StateId)>() * self.transitions.capacity();
+        let accept_size         = size_of::<Option<OutputSymbol>>() * self.accept.capacity();
+        let default_size        = size_of::<Option<StateId>>() * self.default_transitions.capacity();
 
-            if range.includes(&symbol) {
-                // Found a transition to a new state: result will be `More(new state)`
-                let new_count = self.count+1;
+        state_size + transitions_size + accept_size + default_size
+    }
+
+    ///
+    /// Returns true if this DFA can never accept any input (no accepting state can be reached from the start state)
+    ///
+    pub fn is_empty_language(&self) -> bool {
+        // A DFA with no states can't accept anything
+        if self.accept.is_empty() {
+            return true;
+        }
+
+        let mut visited = vec![false; self.accept.len()];
+        let mut waiting = vec![0];
+
+        visited[0] = true;
+
+        while let Some(state) = waiting.pop() {
+            if self.accept[state].is_some() {
+                return false;
+            }
+
+            let targets = self.transitions[self.states[state]..self.states[state+1]].iter()
+                .map(|&(_, target_state)| target_state)
+                .chain(self.default_transition_for_state(state as StateId));
+
+            for target_state in targets {
+                let target_state = target_state as usize;
 
-                // If the new state is an accepting state, then remember it in case we reach a rejecting state later
-                let new_accept = if let Some(ref output) = self.state_machine.accept[new_state as usize] {
-                    Some((new_count, output))
+                if !visited[target_state] {
+                    visited[target_state] = true;
+                    waiting.push(target_state);
+                }
+            }
+        }
+
+        true
+    }
+
+    ///
+    /// Returns true if there's a loop in this DFA (so that it can match strings of any length)
+    ///
+    pub fn has_cycle(&self) -> bool {
+        !self.cycle_states().is_empty()
+    }
+
+    ///
+    /// Returns the states that are part of a loop in this DFA, in order
+    ///
+    /// These are the states that can be returned to after reading more symbols, so they usually correspond to the parts of
+    /// a pattern that are repeated with `repeat_forever`. This finds the strongly connected components of the DFA using
+    /// Tarjan's algorithm: states in a component with more than one state, or with a transition back to themselves, are in
+    /// a loop.
+    ///
+    pub fn cycle_states(&self) -> Vec<StateId> {
+        let num_states          = self.accept.len();
+        let mut index           = vec![None; num_states];
+        let mut lowlink         = vec![0; num_states];
+        let mut on_stack        = vec![false; num_states];
+        let mut component_stack = vec![];
+        let mut next_index      = 0;
+        let mut in_cycle        = vec![false; num_states];
+
+        let targets_for_state = |state: usize| -> Vec<usize> {
+            self.transitions[self.states[state]..self.states[state+1]].iter()
+                .map(|&(_, target_state)| target_state)
+                .chain(self.default_transition_for_state(state as StateId))
+                .map(|target_state| target_state as usize)
+                .collect()
+        };
+
+        for root in 0..num_states {
+            if index[root].is_some() {
+                continue;
+            }
+
+            // Depth-first search, keeping an explicit stack of states along with the targets that remain to be visited
+            let mut search = vec![(root, targets_for_state(root), 0)];
+            index[root]     = Some(next_index);
+            lowlink[root]   = next_index;
+            next_index      += 1;
+            component_stack.push(root);
+            on_stack[root]  = true;
+
+            while let Some((state, targets, next_target)) = search.pop() {
+                if next_target < targets.len() {
+                    let target = targets[next_target];
+                    search.push((state, targets, next_target+1));
+
+                    match index[target] {
+                        None => {
+                            // Visit this state next
+                            index[target]       = Some(next_index);
+                            lowlink[target]     = next_index;
+                            next_index          += 1;
+                            component_stack.push(target);
+                            on_stack[target]    = true;
+
+                            search.push((target, targets_for_state(target), 0));
+                        },
+
+                        Some(target_index) => {
+                            if on_stack[target] {
+                                lowlink[state] = lowlink[state].min(target_index);
+                            }
+                        }
+                    }
                 } else {
-                    self.accept
-                };
+                    // Finished with this state: update its parent and pop its component if it's the root of one
+                    if let Some(&(parent, _, _)) = search.last() {
+                        lowlink[parent] = lowlink[parent].min(lowlink[state]);
+                    }
 
-                // Action is 'More'
-                // TODO: might be an option to return Accept or Reject here if the new state has no transitions
-                // (Possible performance advantage, but depends on the regex and input conditions)
-                return More(SymbolRangeState { state: new_state, count: new_count, accept: new_accept, state_machine: self.state_machine });
+                    if Some(lowlink[state]) == index[state] {
+                        let mut component = vec![];
+
+                        loop {
+                            let member          = component_stack.pop().unwrap();
+                            on_stack[member]    = false;
+                            component.push(member);
+
+                            if member == state { break; }
+                        }
+
+                        let is_cycle = component.len() > 1 || targets.contains(&state);
+                        if is_cycle {
+                            for member in component {
+                                in_cycle[member] = true;
+                            }
+                        }
+                    }
+                }
             }
         }
 
-        // No matches: finish the state machine
-        self.finish()
+        (0..num_states).filter(|&state| in_cycle[state]).map(|state| state as StateId).collect()
     }
 
-    fn finish(self) -> MatchAction<'a, OutputSymbol, Self> {
-        if let Some(accept_state) = self.accept {
-            // We found an accepting state earlier on, so return that
-            let (length, symbol) = accept_state;
-            Accept(length, symbol)
-        } else {
-            // No accepting state was found while this state machine was running
-            Reject
+    ///
+    /// Returns true if this DFA accepts the empty string
+    ///
+    pub fn matches_empty_string(&self) -> bool {
+        self.accept.first().map(|output| output.is_some()).unwrap_or(false)
+    }
+
+    ///
+    /// Creates a DFA that matches the same strings as this one, with each output symbol replaced by the result of a function
+    ///
+    /// The states and transitions are left as they are, so if several output symbols are mapped to the same value, the
+    /// result may have more states than is necessary (`minimize()` can be used to remove these).
+    ///
+    pub fn map_accept<NewOutputSymbol, MapFn: Fn(OutputSymbol) -> NewOutputSymbol>(self, map_fn: MapFn) -> SymbolRangeDfa<InputSymbol, NewOutputSymbol> {
+        SymbolRangeDfa {
+            states:                 self.states,
+            transitions:            self.transitions,
+            accept:                 self.accept.into_iter().map(|output| output.map(|output| map_fn(output))).collect(),
+            default_transitions:    self.default_transitions
         }
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::super::dfa_builder::*;
-    use super::super::symbol_range::*;
-    use super::super::pattern_matcher::*;
-    use super::super::state_machine::*;
-    use super::*;
+impl<InputSymbol: Ord+Debug, OutputSymbol: Debug> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+    ///
+    /// Returns a human-readable listing of the states in this DFA
+    ///
+    /// Each state is described on its own line, along with its transitions and output symbol, in the form
+    /// `state 0: ['a'-'c'] -> 1, ['x'] -> 2  (accept: Token)`. This is intended for quick inspection (eg, in test output).
+    ///
+    pub fn describe(&self) -> String {
+        let mut result = String::new();
 
-    #[test]
-    fn can_build_state_machine() {
-        let mut builder = SymbolRangeDfaBuilder::new();
+        for state in 0..self.accept.len() {
+            result.push_str(&format!("state {}:", state));
 
-        // State 0: '0', move to state 1
-        builder.start_state();
-        builder.transition(SymbolRange::new(0, 0), 1);
+            // Transitions for this state
+            let mut transitions: Vec<_> = self.transitions[self.states[state]..self.states[state+1]].iter()
+                .map(|&(ref range, target_state)| {
+                    if range.lowest == range.highest {
+                        format!("[{:?}] -> {}", range.lowest, target_state)
+                    } else {
+                        format!("[{:?}-{:?}] -> {}", range.lowest, range.highest, target_state)
+                    }
+                })
+                .collect();
 
-        // State 1: accept, output symbol "Success"
-        builder.start_state();
-        builder.accept("Success");
+            if let Some(default_target) = self.default_transition_for_state(state as StateId) {
+                transitions.push(format!("* -> {}", default_target));
+            }
 
-        // Create the state machine  
-        let state_machine = builder.build();
+            if !transitions.is_empty() {
+                result.push_str(" ");
+                result.push_str(&transitions.join(", "));
+            }
 
-        assert!(state_machine.count_states() == 2);
-        assert!(state_machine.output_symbol_for_state(0) == None);
-        assert!(state_machine.output_symbol_for_state(1) == Some(&"Success"));
-        assert!(state_machine.get_transitions_for_state(0) == vec![(SymbolRange::new(0,0), 1)]);
+            // Output symbol, if this is an accepting state
+            if let Some(ref output_symbol) = self.accept[state] {
+                result.push_str(&format!("  (accept: {:?})", output_symbol));
+            }
+
+            result.push_str("\n");
+        }
+
+        result
     }
+}
 
-    #[test]
-    fn can_accept_single_symbol() {
-        let mut builder = SymbolRangeDfaBuilder::new();
+impl<InputSymbol: Ord+Clone, OutputSymbol> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+    ///
+    /// Runs this DFA against a symbol reader, returning the states that it visited along with the symbol that was read in each state
+    ///
+    /// The trace ends when the DFA reaches a state with no transition for the symbol that was read (the last symbol in the
+    /// trace), or when the reader runs out of symbols (the last symbol in the trace will be `None`). This is useful for finding
+    /// out why an input did not match a pattern.
+    ///
+    pub fn trace(&self, reader: &mut SymbolReader<InputSymbol>) -> Vec<(StateId, Option<InputSymbol>)> {
+        let mut result  = vec![];
+        let mut state   = 0;
 
-        // State 0: '0', move to state 1
-        builder.start_state();
-        builder.transition(SymbolRange::new(0, 0), 1);
+        loop {
+            let symbol = reader.next_symbol();
+            result.push((state, symbol.clone()));
 
-        // State 1: accept, output symbol "Success"
-        builder.start_state();
-        builder.accept("Success");
+            // Stop at the end of the input
+            let symbol = match symbol {
+                Some(symbol)    => symbol,
+                None            => break
+            };
 
-        // Create the state machine  
-        let state_machine = builder.build();
+            // Stop if there's no transition for this symbol
+            let start_transition    = self.states[state as usize];
+            let end_transition      = self.states[state as usize+1];
+            let next_state          = self.transitions[start_transition..end_transition].iter()
+                .find(|&&(ref range, _)| range.includes(&symbol))
+                .map(|&(_, target_state)| target_state)
+                .or_else(|| self.default_transition_for_state(state));
 
-        // Run the first state
-        let mut action = state_machine.start();
+            match next_state {
+                Some(next_state)    => state = next_state,
+                None                => break
+            }
+        }
 
-        if let More(next_state) = action {
-            action = next_state.next(0);
+        result
+    }
+
+    ///
+    /// Matches this DFA against a symbol reader, returning the position where the input diverged from the language if it
+    /// doesn't match
+    ///
+    /// On success, this returns the length of the longest match along with its output symbol, in the same way as `match_pattern`.
+    /// If there's no match, the error is the number of symbols that were read before the DFA rejected the input (either because
+    /// the next symbol had no transition or because the input ended).
+    ///
+    pub fn match_or_error(&self, reader: &mut SymbolReader<InputSymbol>) -> Result<(usize, &OutputSymbol), usize> {
+        let trace = self.trace(reader);
+
+        // Entry n in the trace is the state after reading n symbols: find the last one that was accepting
+        let longest_match = trace.iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(length, &(state, _))| self.accept[state as usize].as_ref().map(|output| (length, output)))
+            .next();
+
+        match longest_match {
+            Some(result)    => Ok(result),
+            None            => Err(trace.len()-1)
         }
+    }
 
-        if let More(next_state) = action {
-            action = next_state.next(0);
+    ///
+    /// Removes any states that can't be reached from the start state
+    ///
+    /// DFAs generated by this library never contain unreachable states, but DFAs that have been deserialized or built by
+    /// hand might. The surviving states are renumbered in the order they're found by a breadth-first search from state 0,
+    /// so the start state keeps its ID.
+    ///
+    pub fn remove_unreachable(&mut self) {
+        let num_states      = self.accept.len();
+        let mut new_ids     = vec![None; num_states];
+        let mut old_ids     = vec![0];
+        let mut waiting     = VecDeque::new();
 
-            // Should have reached an accepting state (read one character)
-            if let Accept(count, symbol) = action {
-                // One symbol accepted
-                assert!(count == 1);
+        new_ids[0] = Some(0);
+        waiting.push_back(0);
 
-                // Output symbol correct
-                assert!(symbol == &"Success");
-            } else {
-                // Should have accepted here (the second '0' is rejected)
-                assert!(false);
+        // Assign new IDs to the states in the order that they're discovered
+        while let Some(state) = waiting.pop_front() {
+            let targets = self.transitions[self.states[state]..self.states[state+1]].iter()
+                .map(|&(_, target_state)| target_state)
+                .chain(self.default_transition_for_state(state as StateId));
+
+            for target_state in targets {
+                let target_state = target_state as usize;
+
+                if new_ids[target_state].is_none() {
+                    new_ids[target_state] = Some(old_ids.len() as StateId);
+                    old_ids.push(target_state);
+                    waiting.push_back(target_state);
+                }
             }
-        } else {
-            // State machine did not accept the character
-            assert!(false);
         }
+
+        // Nothing to do if every state is reachable
+        if old_ids.len() == num_states {
+            return;
+        }
+
+        // Rebuild the tables with just the reachable states
+        let mut old_accept          = replace(&mut self.accept, vec![]);
+        let mut states              = vec![];
+        let mut transitions         = vec![];
+        let mut default_transitions = vec![];
+
+        for old_state in old_ids {
+            states.push(transitions.len());
+
+            for transit_index in self.states[old_state]..self.states[old_state+1] {
+                let (ref range, target_state) = self.transitions[transit_index];
+                transitions.push((range.clone(), new_ids[target_state as usize].unwrap()));
+            }
+
+            self.accept.push(old_accept[old_state].take());
+            default_transitions.push(self.default_transition_for_state(old_state as StateId).map(|target_state| new_ids[target_state as usize].unwrap()));
+        }
+
+        states.push(transitions.len());
+
+        self.states                 = states;
+        self.transitions            = transitions;
+        self.default_transitions    = default_transitions;
+    }
+}
+
+impl<InputSymbol: Ord+Clone+Countable, OutputSymbol> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+    ///
+    /// Adds a state that matches the longest run of symbols that can't start a match, producing an error output symbol
+    ///
+    /// The start state is given a default transition to a new error state, which accepts with `error_output`. The error
+    /// state keeps following its own default transition until it sees a symbol that the start state has a transition for.
+    /// This means that input that would otherwise be rejected is matched in one go instead of a symbol at a time. If the
+    /// start state already has a default transition then every symbol can start a match, so the DFA is returned unchanged.
+    ///
+    pub fn with_error_state(self, error_output: OutputSymbol) -> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+        let stop_symbols = self.get_transitions_for_state(0).into_iter().map(|(range, _)| range).collect::<Vec<_>>();
+
+        self.with_error_state_stopping_at(error_output, &stop_symbols)
+    }
+
+    ///
+    /// Adds an error state in the same way as `with_error_state()`, but ends the run of symbols at a specified set of symbols
+    ///
+    /// This is useful when something other than this DFA may be matched after the error, such as another DFA used for
+    /// the same input. `stop_symbols` must be in order and must not overlap. They don't have to be the same as the symbols
+    /// the start state has transitions for: a stop symbol with no transition from the start state still begins an error
+    /// when it is the first symbol of a match, and any other symbol continues an error even if the start state has a
+    /// transition for it.
+    ///
+    pub fn with_error_state_stopping_at(mut self, error_output: OutputSymbol, stop_symbols: &[SymbolRange<InputSymbol>]) -> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+        if self.default_transition_for_state(0).is_some() {
+            return self;
+        }
+
+        let num_states  = self.accept.len();
+        let error_state = num_states as StateId;
+        let dead_state  = error_state + 1;
+
+        // The error state stops (by moving to a dead state) on any of the stop symbols
+        for range in stop_symbols {
+            self.transitions.push((range.clone(), dead_state));
+        }
+
+        // The final entry in the states list is the start of the error state's transitions, so add the dead state and a new cap
+        self.states.push(self.transitions.len());
+        self.states.push(self.transitions.len());
+        self.accept.push(Some(error_output));
+        self.accept.push(None);
+
+        self.default_transitions.resize(num_states, None);
+        self.default_transitions[0] = Some(error_state);
+        self.default_transitions.push(Some(error_state));
+        self.default_transitions.push(None);
+
+        self
+    }
+
+    ///
+    /// Merges any adjacent ranges in each state that move to the same target state into a single transition
+    ///
+    /// DFAs produced by the compiler already have their transitions merged, but DFAs produced in other ways (for example,
+    /// by `difference()` or by building one by hand) may have transitions that can be combined. Fewer transitions makes
+    /// the DFA smaller and quicker to match. The order of the transitions in each state is left alone.
+    ///
+    pub fn merge_transitions(&mut self) {
+        let mut states                                                = Vec::with_capacity(self.states.len());
+        let mut transitions: Vec<(SymbolRange<InputSymbol>, StateId)> = Vec::with_capacity(self.transitions.len());
+
+        for state in 0..self.states.len().saturating_sub(1) {
+            states.push(transitions.len());
+            let first_transition = transitions.len();
+
+            for &(ref range, target_state) in self.transitions[self.states[state]..self.states[state+1]].iter() {
+                // Extend the previous range if it's adjacent and goes to the same place
+                if let Some(&mut (ref mut last_range, last_target)) = transitions[first_transition..].last_mut() {
+                    if last_target == target_state && last_range.highest.next() == range.lowest {
+                        last_range.highest = range.highest.clone();
+                        continue;
+                    }
+                }
+
+                transitions.push((range.clone(), target_state));
+            }
+        }
+
+        states.push(transitions.len());
+
+        self.states         = states;
+        self.transitions    = transitions;
+    }
+
+    ///
+    /// Sorts the transitions for every state and merges any adjacent ranges that move to the same state
+    ///
+    /// DFAs that only differ in how their symbol ranges are split up (eg, `[a-c]` instead of `[a-a][b-c]`) will compare as
+    /// equal after they've been canonicalized. This doesn't renumber the states, so DFAs whose states are in a different
+    /// order will still be different.
+    ///
+    pub fn canonicalize(&mut self) {
+        for state in 0..self.states.len().saturating_sub(1) {
+            self.transitions[self.states[state]..self.states[state+1]].sort_by(|a, b| a.0.lowest.cmp(&b.0.lowest));
+        }
+
+        self.merge_transitions();
+    }
+}
+
+impl<InputSymbol: Ord+Clone+Countable, OutputSymbol: Ord+Clone> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+    ///
+    /// Returns the transitions for a state with the target states replaced by their class, merging any adjacent ranges
+    /// that end up with the same target
+    ///
+    fn transitions_for_classes(&self, state: usize, classes: &[usize]) -> Vec<(SymbolRange<InputSymbol>, usize)> {
+        let mut result: Vec<(SymbolRange<InputSymbol>, usize)> = vec![];
+
+        for transit_index in self.states[state]..self.states[state+1] {
+            let (ref range, target_state)   = self.transitions[transit_index];
+            let target_class                = classes[target_state as usize];
+
+            // Extend the previous range if it's adjacent and goes to the same place
+            if let Some(&mut (ref mut last_range, last_class)) = result.last_mut() {
+                if last_class == target_class && last_range.highest.next() == range.lowest {
+                    last_range.highest = range.highest.clone();
+                    continue;
+                }
+            }
+
+            result.push((range.clone(), target_class));
+        }
+
+        result
+    }
+
+    ///
+    /// Generates a DFA that matches the same language as this one with the minimum number of states
+    ///
+    /// States are merged if they produce the same output symbol and always move to equivalent states for every input
+    /// symbol.
+    ///
+    pub fn minimize(&self) -> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+        let num_states = self.states.len().saturating_sub(1);
+
+        // Initially, states are divided up by their output symbol
+        let mut classes     = vec![0; num_states];
+        let mut num_classes = {
+            let mut class_for_output = BTreeMap::new();
+
+            for state in 0..num_states {
+                let next_class  = class_for_output.len();
+                classes[state]  = *class_for_output.entry(self.accept[state].as_ref()).or_insert(next_class);
+            }
+
+            class_for_output.len()
+        };
+
+        // Divide the classes further until every state in a class has equivalent transitions
+        loop {
+            let mut class_for_signature = BTreeMap::new();
+            let mut new_classes         = vec![0; num_states];
+
+            for state in 0..num_states {
+                let default_class   = self.default_transition_for_state(state as StateId).map(|target_state| classes[target_state as usize]);
+                let signature       = (classes[state], self.transitions_for_classes(state, &classes), default_class);
+                let next_class      = class_for_signature.len();
+                new_classes[state]  = *class_for_signature.entry(signature).or_insert(next_class);
+            }
+
+            let new_num_classes = class_for_signature.len();
+            classes = new_classes;
+
+            if new_num_classes == num_classes {
+                break;
+            }
+
+            num_classes = new_num_classes;
+        }
+
+        // Number the new states in order, so that the class containing state 0 is the new start state
+        let mut new_state_for_class = vec![None; num_classes];
+        let mut representatives     = vec![];
+
+        for state in 0..num_states {
+            if new_state_for_class[classes[state]].is_none() {
+                new_state_for_class[classes[state]] = Some(representatives.len() as StateId);
+                representatives.push(state);
+            }
+        }
+
+        // Build the new DFA from the representative states
+        let mut builder = SymbolRangeDfaBuilder::new();
+
+        for state in representatives {
+            builder.start_state();
+
+            if let Some(ref output) = self.accept[state] {
+                builder.accept(output.clone());
+            }
+
+            for (range, target_class) in self.transitions_for_classes(state, &classes) {
+                builder.transition(range, new_state_for_class[target_class].unwrap());
+            }
+
+            if let Some(default_target) = self.default_transition_for_state(state as StateId) {
+                builder.default_transition(new_state_for_class[classes[default_target as usize]].unwrap());
+            }
+        }
+
+        builder.build()
+    }
+
+    ///
+    /// Generates a DFA that accepts the strings accepted by this DFA that are not accepted by another DFA
+    ///
+    /// The output symbols are the same as the output symbols for this DFA. This is done by running the two DFAs side by side:
+    /// the states of the result represent a state in this DFA along with the state of the other DFA, or `None` if the other
+    /// DFA has rejected the input.
+    ///
+    pub fn difference<OtherOutputSymbol>(&self, other: &SymbolRangeDfa<InputSymbol, OtherOutputSymbol>) -> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+        let mut state_ids       = HashMap::new();
+        let mut state_pairs     = vec![(0, Some(0))];
+        let mut builder         = SymbolRangeDfaBuilder::new();
+
+        state_ids.insert((0, Some(0)), 0);
+
+        // States are built in the order that they're discovered, so their IDs are their index in state_pairs
+        let mut next_state = 0;
+
+        while next_state < state_pairs.len() {
+            let (our_state, other_state) = state_pairs[next_state];
+            next_state += 1;
+
+            builder.start_state();
+
+            // Accept if we accept and the other DFA does not
+            let other_accepts = other_state.map(|other_state| other.accept[other_state].is_some()).unwrap_or(false);
+
+            if let Some(ref output) = self.accept[our_state] {
+                if !other_accepts {
+                    builder.accept(output.clone());
+                }
+            }
+
+            // Split the symbols used by either DFA into pieces that move both DFAs to a single state each
+            let our_transitions     = &self.transitions[self.states[our_state]..self.states[our_state+1]];
+            let other_transitions   = match other_state {
+                Some(other_state)   => &other.transitions[other.states[other_state]..other.states[other_state+1]],
+                None                => &other.transitions[0..0]
+            };
+
+            let our_default         = self.default_transition_for_state(our_state as StateId);
+            let other_default       = other_state.and_then(|other_state| other.default_transition_for_state(other_state as StateId));
+
+            let mut symbols = SymbolMap::new();
+            for &(ref range, _) in our_transitions.iter() {
+                symbols.add_range(range);
+            }
+
+            // The other DFA's ranges only matter where this DFA can move (they'll be treated as part of the default otherwise)
+            for &(ref range, _) in other_transitions.iter() {
+                if our_default.is_some() || our_transitions.iter().any(|&(ref our_range, _)| our_range.overlaps(range)) {
+                    symbols.add_range(range);
+                }
+            }
+
+            // Pieces of the non-overlapping map lie entirely within any of the original ranges that they overlap
+            let target_for_piece = |transitions: &[(SymbolRange<InputSymbol>, StateId)], piece: &SymbolRange<InputSymbol>| {
+                transitions.iter()
+                    .find(|&&(ref range, _)| range.includes(&piece.lowest))
+                    .map(|&(_, target_state)| target_state as usize)
+            };
+
+            let mut add_target = |state_pairs: &mut Vec<(usize, Option<usize>)>, target_pair: (usize, Option<usize>)| {
+                *state_ids.entry(target_pair).or_insert_with(|| {
+                    state_pairs.push(target_pair);
+                    (state_pairs.len()-1) as StateId
+                })
+            };
+
+            for piece in symbols.to_non_overlapping_map().ranges() {
+                let our_target = match target_for_piece(our_transitions, piece).or(our_default.map(|target_state| target_state as usize)) {
+                    Some(our_target)    => our_target,
+                    None                => continue
+                };
+                let other_target = target_for_piece(other_transitions, piece).or(other_default.map(|target_state| target_state as usize));
+
+                let target_state = add_target(&mut state_pairs, (our_target, other_target));
+                builder.transition(piece.clone(), target_state);
+            }
+
+            // Symbols that aren't in any of the ranges follow the default transitions of both DFAs
+            if let Some(our_default) = our_default {
+                let target_state = add_target(&mut state_pairs, (our_default as usize, other_default.map(|target_state| target_state as usize)));
+                builder.default_transition(target_state);
+            }
+        }
+
+        builder.build()
+    }
+}
+
+///
+/// Result of feeding a symbol to a `ResumableMatcher`
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FeedResult<'a, OutputSymbol: 'a> {
+    /// The match is not finished yet: more symbols can be fed to the matcher
+    Continue,
+
+    /// The match has finished, with the length of the match and its output symbol if it was accepted
+    ///
+    /// The matcher may have been fed symbols after the end of the accepted match: these should be fed to the next matcher.
+    Complete(Option<(usize, &'a OutputSymbol)>)
+}
+
+///
+/// Matches a DFA against symbols that are supplied one at a time
+///
+/// This is useful when the input arrives in several chunks (for instance, when reading from a socket), as the matcher keeps
+/// its state between calls to `feed`.
+///
+pub struct ResumableMatcher<'a, InputSymbol: Ord+'a, OutputSymbol: 'a> {
+    /// The state of the matcher, if it's still running
+    state: Option<SymbolRangeState<'a, InputSymbol, OutputSymbol>>,
+
+    /// The result of the match once it has completed
+    result: Option<(usize, &'a OutputSymbol)>
+}
+
+impl<'a, InputSymbol: Ord+'a, OutputSymbol: 'a> ResumableMatcher<'a, InputSymbol, OutputSymbol> {
+    ///
+    /// Stores the result of an action that has finished matching
+    ///
+    fn complete(&mut self, action: MatchAction<'a, OutputSymbol, SymbolRangeState<'a, InputSymbol, OutputSymbol>>) -> Option<(usize, &'a OutputSymbol)> {
+        self.result = match action {
+            Accept(length, output)  => Some((length, output)),
+            _                       => None
+        };
+
+        self.result
+    }
+
+    ///
+    /// Matches the next symbol
+    ///
+    pub fn feed(&mut self, symbol: InputSymbol) -> FeedResult<'a, OutputSymbol> {
+        if let Some(state) = self.state.take() {
+            match state.next(symbol) {
+                More(next_state)    => { self.state = Some(next_state); FeedResult::Continue },
+                finished            => FeedResult::Complete(self.complete(finished))
+            }
+        } else {
+            // Already finished
+            FeedResult::Complete(self.result)
+        }
+    }
+
+    ///
+    /// Indicates that there are no more symbols, and returns the final result of the match
+    ///
+    pub fn finish(&mut self) -> Option<(usize, &'a OutputSymbol)> {
+        if let Some(state) = self.state.take() {
+            let finished = state.finish();
+            self.complete(finished)
+        } else {
+            self.result
+        }
+    }
+}
+
+impl<InputSymbol: Ord, OutputSymbol> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+    ///
+    /// Creates a matcher for this DFA that can be fed symbols one at a time
+    ///
+    pub fn matcher<'a>(&'a self) -> ResumableMatcher<'a, InputSymbol, OutputSymbol> {
+        match self.start() {
+            More(state) => ResumableMatcher { state: Some(state), result: None },
+            _           => panic!("Unexpected start state for SymbolRangeDfa")
+        }
+    }
+}
+
+impl<'a, InputSymbol: Ord+'a, OutputSymbol: 'a> SymbolRangeState<'a, InputSymbol, OutputSymbol> {
+    ///
+    /// Returns the state after reading a symbol that moves the state machine to the specified state
+    ///
+    #[inline]
+    fn move_to(self, new_state: StateId) -> SymbolRangeState<'a, InputSymbol, OutputSymbol> {
+        let new_count = self.count+1;
+
+        // If the new state is an accepting state, then remember it in case we reach a rejecting state later
+        let new_accept = if let Some(ref output) = self.state_machine.accept[new_state as usize] {
+            Some((new_count, output))
+        } else {
+            self.accept
+        };
+
+        SymbolRangeState { state: new_state, count: new_count, accept: new_accept, state_machine: self.state_machine }
+    }
+}
+
+impl<'a, InputSymbol: Ord+'a, OutputSymbol: 'a> MatchingState<'a, InputSymbol, OutputSymbol> for SymbolRangeState<'a, InputSymbol, OutputSymbol> {
+    fn next(self, symbol: InputSymbol) -> MatchAction<'a, OutputSymbol, Self> {
+        // The transition range is defined by the current state
+        let start_transition    = self.state_machine.states[self.state as usize];
+        let end_transition      = self.state_machine.states[self.state as usize+1];
+
+        // See if there is an input symbol matching this transition
+        // TODO: consider binary searching for states with large numbers of transitions? (Do these occur regularly in patterns that people use?)
+        for transit in start_transition..end_transition {
+            // Test this transition
+            let (ref range, new_state) = self.state_machine.transitions[transit];
+
+            if range.includes(&symbol) {
+                // Found a transition to a new state: result will be `More(new state)`
+                // TODO: might be an option to return Accept or Reject here if the new state has no transitions
+                // (Possible performance advantage, but depends on the regex and input conditions)
+                return More(self.move_to(new_state));
+            }
+        }
+
+        // No matches: follow the default transition if there is one, otherwise finish the state machine
+        match self.state_machine.default_transition_for_state(self.state) {
+            Some(default_target)    => More(self.move_to(default_target)),
+            None                    => self.finish()
+        }
+    }
+
+    fn finish(self) -> MatchAction<'a, OutputSymbol, Self> {
+        if let Some(accept_state) = self.accept {
+            // We found an accepting state earlier on, so return that
+            let (length, symbol) = accept_state;
+            Accept(length, symbol)
+        } else {
+            // No accepting state was found while this state machine was running
+            Reject
+        }
+    }
+}
+
+impl<'a, InputSymbol: Ord+'a, OutputSymbol: 'a> PeekableMatchingState<'a, InputSymbol, OutputSymbol> for SymbolRangeState<'a, InputSymbol, OutputSymbol> {
+    fn peek_accept(&self) -> Option<(usize, &'a OutputSymbol)> {
+        // The most recent accepting state is the current state if it was reached after the last symbol
+        match self.accept {
+            Some((length, symbol)) if length == self.count  => Some((length, symbol)),
+            _                                               => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::dfa_builder::*;
+    use super::super::symbol_range::*;
+    use super::super::pattern_matcher::*;
+    use super::super::state_machine::*;
+    use super::super::regular_pattern::*;
+    use super::super::prepare::*;
+    use super::super::matches::*;
+    use super::super::tokenizer::*;
+    use super::*;
+
+    #[test]
+    fn dfa_with_empty_states_list_can_be_transformed() {
+        // Deserialized DFAs might not have the 'cap' entry at the end of the states list
+        let empty: SymbolRangeDfa<char, ()> = SymbolRangeDfa {
+            states:                 vec![],
+            transitions:            vec![],
+            accept:                 vec![],
+            default_transitions:    vec![]
+        };
+
+        assert!(empty.count_states() == 0);
+        assert!(empty.minimize().count_states() == 0);
+
+        let mut merged = empty.clone();
+        merged.merge_transitions();
+        assert!(merged.count_states() == 0);
+
+        let mut canonical = empty.clone();
+        canonical.canonicalize();
+        assert!(canonical.count_states() == 0);
+    }
+
+    #[test]
+    fn canonicalize_merges_split_ranges() {
+        let split: SymbolRangeDfa<char, ()> = SymbolRangeDfa {
+            states:                 vec![0, 3, 3],
+            transitions:            vec![(SymbolRange::new('b', 'b'), 1), (SymbolRange::new('a', 'a'), 1), (SymbolRange::new('c', 'c'), 1)],
+            accept:                 vec![None, Some(())],
+            default_transitions:    vec![]
+        };
+        let merged: SymbolRangeDfa<char, ()> = SymbolRangeDfa {
+            states:                 vec![0, 1, 1],
+            transitions:            vec![(SymbolRange::new('a', 'c'), 1)],
+            accept:                 vec![None, Some(())],
+            default_transitions:    vec![]
+        };
+
+        let mut canonical = split.clone();
+        canonical.canonicalize();
+
+        assert!(split != merged);
+        assert!(canonical == merged);
+
+        for input in vec!["a", "b", "c", "d", ""] {
+            assert!(matches_prepared(input, &canonical) == matches_prepared(input, &split));
+        }
+    }
+
+    #[test]
+    fn merge_transitions_combines_adjacent_ranges() {
+        let mut builder = SymbolRangeDfaBuilder::new();
+
+        builder.start_state();
+        builder.transition(SymbolRange::new('x', 'x'), 1);
+
+        // The builder only merges transitions when the next state is started, so these are left split in the last state
+        builder.start_state();
+        builder.accept("Word");
+        builder.transition(SymbolRange::new('a', 'b'), 1);
+        builder.transition(SymbolRange::new('c', 'd'), 1);
+        builder.transition(SymbolRange::new('f', 'g'), 1);
+
+        let split       = builder.build();
+        let mut merged  = split.clone();
+        merged.merge_transitions();
+
+        assert!(split.get_transitions_for_state(1).len() == 3);
+        assert!(merged.get_transitions_for_state(1) == vec![(SymbolRange::new('a', 'd'), 1), (SymbolRange::new('f', 'g'), 1)]);
+
+        for input in vec!["xabcd", "xdcba", "xe", "xfg", "x", ""] {
+            assert!(matches_prepared(input, &merged) == matches_prepared(input, &split));
+        }
+    }
+
+    #[test]
+    fn canonicalize_keeps_ranges_to_different_states() {
+        let mut dfa = exactly("ab").or("b").prepare_to_match();
+        let before  = dfa.clone();
+
+        dfa.canonicalize();
+
+        assert!(dfa == before);
+        assert!(matches_prepared("ab", &dfa) == Some(2));
+        assert!(matches_prepared("b", &dfa) == Some(1));
+    }
+
+    ///
+    /// Builds a DFA that matches 'x' followed by any symbol, where anything in [a-c] moves to an accepting state and anything
+    /// else moves to a separate accepting state via a default transition
+    ///
+    fn default_transition_dfa() -> SymbolRangeDfa<char, &'static str> {
+        let mut builder = SymbolRangeDfaBuilder::new();
+
+        builder.start_state();
+        builder.transition(SymbolRange::new('x', 'x'), 1);
+
+        builder.start_state();
+        builder.transition(SymbolRange::new('a', 'c'), 2);
+        builder.default_transition(3);
+
+        builder.start_state();
+        builder.accept("Range");
+
+        builder.start_state();
+        builder.accept("Other");
+
+        builder.build()
+    }
+
+    #[test]
+    fn explicit_transition_takes_priority_over_default() {
+        let dfa = default_transition_dfa();
+
+        assert!(dfa.match_or_error(&mut "xb".read_symbols()) == Ok((2, &"Range")));
+        assert!(dfa.default_transition_for_state(1) == Some(3));
+        assert!(dfa.default_transition_for_state(0) == None);
+    }
+
+    #[test]
+    fn unmatched_symbol_follows_default_transition() {
+        let dfa = default_transition_dfa();
+
+        assert!(dfa.match_or_error(&mut "xz".read_symbols()) == Ok((2, &"Other")));
+        assert!(dfa.match_or_error(&mut "x!".read_symbols()) == Ok((2, &"Other")));
+
+        assert!(matches_prepared("xz", &dfa) == Some(2));
+
+        // State 0 has no default, so it still rejects symbols it has no transition for
+        assert!(matches_prepared("zz", &dfa) == None);
+        assert!(dfa.match_or_error(&mut "zz".read_symbols()) == Err(0));
+    }
+
+    #[test]
+    fn default_transitions_survive_minimize_and_remove_unreachable() {
+        let mut dfa = default_transition_dfa().minimize();
+        dfa.remove_unreachable();
+
+        assert!(dfa.describe().contains("* -> "));
+        assert!(dfa.match_or_error(&mut "xb".read_symbols()) == Ok((2, &"Range")));
+        assert!(dfa.match_or_error(&mut "xq".read_symbols()) == Ok((2, &"Other")));
+        assert!(!dfa.is_empty_language());
+    }
+
+    #[test]
+    fn difference_follows_default_transitions() {
+        // x followed by anything except b
+        let dfa     = default_transition_dfa();
+        let not_b   = dfa.difference(&exactly("xb").prepare_to_match());
+
+        assert!(matches_prepared("xa", &not_b) == Some(2));
+        assert!(matches_prepared("xq", &not_b) == Some(2));
+        assert!(matches_prepared("xb", &not_b) == None);
+    }
+
+    #[test]
+    fn literal_has_no_cycle() {
+        let dfa = exactly("abc").prepare_to_match();
+
+        assert!(!dfa.has_cycle());
+        assert!(dfa.cycle_states() == vec![]);
+    }
+
+    #[test]
+    fn repeat_forever_has_cycle() {
+        let dfa = exactly("abc").repeat_forever(1).compile_to_dfa();
+
+        // The cycle is made up of the states after 'a', 'b' and 'c', and doesn't include the start state
+        assert!(dfa.has_cycle());
+        assert!(dfa.cycle_states() == vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn self_loop_is_a_cycle() {
+        let dfa = exactly("x").append(MatchRange('a', 'z').repeat_forever(0)).append("!").prepare_to_match();
+
+        assert!(dfa.has_cycle());
+        assert!(dfa.cycle_states().len() == 1);
+    }
+
+    #[test]
+    fn can_build_state_machine() {
+        let mut builder = SymbolRangeDfaBuilder::new();
+
+        // State 0: '0', move to state 1
+        builder.start_state();
+        builder.transition(SymbolRange::new(0, 0), 1);
+
+        // State 1: accept, output symbol "Success"
+        builder.start_state();
+        builder.accept("Success");
+
+        // Create the state machine  
+        let state_machine = builder.build();
+
+        assert!(state_machine.count_states() == 2);
+        assert!(state_machine.output_symbol_for_state(0) == None);
+        assert!(state_machine.output_symbol_for_state(1) == Some(&"Success"));
+        assert!(state_machine.get_transitions_for_state(0) == vec![(SymbolRange::new(0,0), 1)]);
+    }
+
+    #[test]
+    fn can_accept_single_symbol() {
+        let mut builder = SymbolRangeDfaBuilder::new();
+
+        // State 0: '0', move to state 1
+        builder.start_state();
+        builder.transition(SymbolRange::new(0, 0), 1);
+
+        // State 1: accept, output symbol "Success"
+        builder.start_state();
+        builder.accept("Success");
+
+        // Create the state machine  
+        let state_machine = builder.build();
+
+        // Run the first state
+        let mut action = state_machine.start();
+
+        if let More(next_state) = action {
+            action = next_state.next(0);
+        }
+
+        if let More(next_state) = action {
+            action = next_state.next(0);
+
+            // Should have reached an accepting state (read one character)
+            if let Accept(count, symbol) = action {
+                // One symbol accepted
+                assert!(count == 1);
+
+                // Output symbol correct
+                assert!(symbol == &"Success");
+            } else {
+                // Should have accepted here (the second '0' is rejected)
+                assert!(false);
+            }
+        } else {
+            // State machine did not accept the character
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn literal_is_not_empty_language() {
+        let dfa = exactly("a").prepare_to_match();
+
+        assert!(!dfa.is_empty_language());
+        assert!(!dfa.matches_empty_string());
+    }
+
+    #[test]
+    fn optional_pattern_matches_empty_string() {
+        let dfa = exactly("a").repeat(0..2).prepare_to_match();
+
+        assert!(!dfa.is_empty_language());
+        assert!(dfa.matches_empty_string());
+    }
+
+    #[test]
+    fn transition_target_agrees_with_transitions() {
+        let dfa = exactly("abc").or(MatchRange('a', 'z').repeat_forever(1)).or(MatchRange('0', '9').append("x")).prepare_to_match();
+
+        for state in 0..dfa.count_states() {
+            let transitions = dfa.get_transitions_for_state(state);
+
+            for symbol in "/09:@abcdyz{x".chars() {
+                let scanned = transitions.iter()
+                    .find(|&&(ref range, _)| range.includes(&symbol))
+                    .map(|&(_, target_state)| target_state);
+
+                assert!(dfa.transition_target(state, &SymbolRange::new(symbol, symbol)) == scanned);
+            }
+        }
+    }
+
+    #[test]
+    fn missing_default_transitions_equal_none() {
+        let dfa: SymbolRangeDfa<char, ()>   = exactly("abc").prepare_to_match();
+        let mut without_defaults            = dfa.clone();
+        without_defaults.default_transitions = vec![];
+
+        assert!(dfa.default_transitions.len() == dfa.accept.len());
+        assert!(dfa == without_defaults);
+        assert!(without_defaults == dfa);
+
+        let with_default = dfa.clone().with_error_state(());
+        assert!(with_default != dfa);
+    }
+
+    #[test]
+    fn default_transitions_are_visible_through_state_machine() {
+        let mut builder = SymbolRangeDfaBuilder::new();
+
+        builder.start_state();
+        builder.transition(SymbolRange::new('a', 'a'), 1);
+        builder.default_transition(2);
+        builder.start_state();
+        builder.accept(1);
+        builder.start_state();
+        builder.accept(2);
+
+        let dfa: SymbolRangeDfa<char, u32> = builder.build();
+        let state_machine: &StateMachine<SymbolRange<char>, u32> = &dfa;
+
+        assert!(state_machine.default_transition_for_state(0) == Some(2));
+        assert!(state_machine.default_transition_for_state(1) == None);
+        assert!(state_machine.transition_target(0, &SymbolRange::new('a', 'a')) == Some(1));
+        assert!(state_machine.transition_target(0, &SymbolRange::new('x', 'z')) == Some(2));
+        assert!(state_machine.transition_target(0, &SymbolRange::new('a', 'b')) == None);
+    }
+
+    #[test]
+    fn transition_target_needs_whole_range() {
+        let dfa = MatchRange('a', 'z').prepare_to_match();
+
+        assert!(dfa.transition_target(0, &SymbolRange::new('c', 'f')) == Some(1));
+        assert!(dfa.transition_target(0, &SymbolRange::new('a', 'z')) == Some(1));
+        assert!(dfa.transition_target(0, &SymbolRange::new('Z', 'b')) == None);
+        assert!(dfa.transition_target(1, &SymbolRange::new('a', 'a')) == None);
+    }
+
+    #[test]
+    fn error_state_matches_run_of_unmatched_symbols() {
+        let dfa: SymbolRangeDfa<char, u32> = exactly("ab").or("c").to_ndfa(1).prepare_to_match();
+        let dfa = dfa.with_error_state(2);
+
+        assert!(dfa.match_or_error(&mut "xyzab".read_symbols()) == Ok((3, &2)));
+        assert!(dfa.match_or_error(&mut "x".read_symbols()) == Ok((1, &2)));
+        assert!(dfa.match_or_error(&mut "abxyz".read_symbols()) == Ok((2, &1)));
+        assert!(dfa.match_or_error(&mut "ax".read_symbols()) == Err(1));
+    }
+
+    #[test]
+    fn error_state_can_stop_at_other_symbols() {
+        let dfa: SymbolRangeDfa<char, u32> = exactly("ab").to_ndfa(1).prepare_to_match();
+        let dfa = dfa.with_error_state_stopping_at(2, &[SymbolRange::new('!', '!'), SymbolRange::new('a', 'a')]);
+
+        assert!(dfa.match_or_error(&mut "xy!ab".read_symbols()) == Ok((2, &2)));
+        assert!(dfa.match_or_error(&mut "!x".read_symbols()) == Ok((2, &2)));
+        assert!(dfa.match_or_error(&mut "xyab".read_symbols()) == Ok((2, &2)));
+    }
+
+    #[test]
+    fn trivial_dfa_has_memory_footprint() {
+        let dfa = exactly("a").prepare_to_match();
+
+        assert!(dfa.count_transitions() == 1);
+        assert!(dfa.memory_footprint() > 0);
+    }
+
+    #[test]
+    fn memory_footprint_grows_with_pattern() {
+        let small = exactly("abc").prepare_to_match();
+        let large = exactly("abcdefghijklmnopqrstuvwxyz").or("0123456789").repeat_forever(1).prepare_to_match();
+
+        assert!(large.count_transitions() > small.count_transitions());
+        assert!(large.memory_footprint() > small.memory_footprint());
+    }
+
+    #[test]
+    fn empty_alternative_is_empty_language() {
+        let dfa: SymbolRangeDfa<char, ()> = MatchAny(vec![]).prepare_to_match();
+
+        assert!(dfa.is_empty_language());
+        assert!(!dfa.matches_empty_string());
+    }
+
+    #[test]
+    fn dfa_with_no_states_is_empty_language() {
+        let builder: SymbolRangeDfaBuilder<u32, ()> = SymbolRangeDfaBuilder::new();
+        let state_machine = builder.build();
+
+        assert!(state_machine.count_states() == 0);
+        assert!(state_machine.is_empty_language());
+        assert!(!state_machine.matches_empty_string());
+    }
+
+    #[test]
+    fn unreachable_accepting_state_is_empty_language() {
+        let mut builder = SymbolRangeDfaBuilder::new();
+
+        // State 0: '0', move to state 1
+        builder.start_state();
+        builder.transition(SymbolRange::new(0, 0), 1);
+
+        // State 1: no transitions
+        builder.start_state();
+
+        // State 2: accepting, but there's no way to get here
+        builder.start_state();
+        builder.accept("Success");
+
+        let state_machine = builder.build();
+
+        assert!(state_machine.is_empty_language());
+    }
+
+    #[test]
+    fn can_remove_unreachable_states() {
+        let mut builder = SymbolRangeDfaBuilder::new();
+
+        // State 0: '0', move to state 2
+        builder.start_state();
+        builder.transition(SymbolRange::new(0, 0), 2);
+
+        // State 1: an island that moves to state 2 on '1' but can't be reached itself
+        builder.start_state();
+        builder.transition(SymbolRange::new(1, 1), 2);
+        builder.accept("Island");
+
+        // State 2: accepting, '0' loops back to state 0
+        builder.start_state();
+        builder.transition(SymbolRange::new(0, 0), 0);
+        builder.accept("Success");
+
+        let mut state_machine = builder.build();
+        let original = state_machine.clone();
+
+        state_machine.remove_unreachable();
+
+        assert!(state_machine.count_states() == 2);
+        assert!(state_machine.output_symbol_for_state(0) == None);
+        assert!(state_machine.output_symbol_for_state(1) == Some(&"Success"));
+        assert!(state_machine.get_transitions_for_state(0) == vec![(SymbolRange::new(0,0), 1)]);
+        assert!(state_machine.get_transitions_for_state(1) == vec![(SymbolRange::new(0,0), 0)]);
+
+        for input in vec![vec![0], vec![0, 0], vec![0, 0, 0], vec![1], vec![0, 1], vec![]] {
+            assert!(matches_prepared(&input, &original) == matches_prepared(&input, &state_machine));
+        }
+    }
+
+    #[test]
+    fn removing_unreachable_states_leaves_reachable_dfa_alone() {
+        let mut dfa     = exactly("abc").or("abd").repeat_forever(1).prepare_to_match();
+        let num_states  = dfa.count_states();
+
+        dfa.remove_unreachable();
+
+        assert!(dfa.count_states() == num_states);
+        assert!(matches_prepared("abcabd", &dfa) == Some(6));
+    }
+
+    #[test]
+    fn match_or_error_reports_rejecting_position() {
+        let dfa = exactly("abc").prepare_to_match();
+
+        assert!(dfa.match_or_error(&mut "abX".read_symbols()) == Err(2));
+        assert!(dfa.match_or_error(&mut "ab".read_symbols()) == Err(2));
+        assert!(dfa.match_or_error(&mut "X".read_symbols()) == Err(0));
+        assert!(dfa.match_or_error(&mut "abc".read_symbols()) == Ok((3, &())));
+    }
+
+    #[test]
+    fn match_or_error_returns_longest_match() {
+        let dfa = exactly("ab").repeat_forever(1).prepare_to_match();
+
+        assert!(dfa.match_or_error(&mut "ababX".read_symbols()) == Ok((4, &())));
+        assert!(dfa.match_or_error(&mut "abaX".read_symbols()) == Ok((2, &())));
+    }
+
+    #[test]
+    fn can_map_output_symbols() {
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+        enum Token {
+            OpenParen,
+            CloseParen,
+            OpenBracket,
+            CloseBracket,
+            Word
+        }
+
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+        enum Category {
+            Bracket,
+            Word
+        }
+
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(exactly("("), Token::OpenParen);
+        token_matcher.add_pattern(exactly(")"), Token::CloseParen);
+        token_matcher.add_pattern(exactly("["), Token::OpenBracket);
+        token_matcher.add_pattern(exactly("]"), Token::CloseBracket);
+        token_matcher.add_pattern(MatchRange('a', 'z').repeat_forever(1), Token::Word);
+
+        let dfa         = token_matcher.prepare_to_match();
+        let num_states  = dfa.count_states();
+        let mapped      = dfa.map_accept(|token| match token {
+            Token::Word => Category::Word,
+            _           => Category::Bracket
+        });
+
+        assert!(mapped.count_states() == num_states);
+
+        for (input, expected) in vec![("(", Category::Bracket), ("]", Category::Bracket), ("abc", Category::Word)] {
+            assert!(match_pattern(mapped.start(), &mut input.read_symbols()).is_accepted(&expected));
+        }
+    }
+
+    #[test]
+    fn can_describe_dfa() {
+        let mut builder = SymbolRangeDfaBuilder::new();
+
+        // State 0: 'a'-'c' moves to state 1, 'x' moves to state 2
+        builder.start_state();
+        builder.transition(SymbolRange::new('a', 'c'), 1);
+        builder.transition(SymbolRange::new('x', 'x'), 2);
+
+        // State 1: accepts 'Range'
+        builder.start_state();
+        builder.accept("Range");
+
+        // State 2: accepts 'X'
+        builder.start_state();
+        builder.accept("X");
+
+        let description = builder.build().describe();
+        let lines: Vec<_> = description.lines().collect();
+
+        assert!(lines == vec![
+            "state 0: ['a'-'c'] -> 1, ['x'] -> 2",
+            "state 1:  (accept: \"Range\")",
+            "state 2:  (accept: \"X\")"
+        ]);
+    }
+
+    #[test]
+    fn cloned_dfa_matches_identically() {
+        let dfa     = exactly("abc").or("abd").repeat_forever(1).prepare_to_match();
+        let cloned  = dfa.clone();
+
+        assert!(cloned.count_states() == dfa.count_states());
+
+        for input in vec!["abc", "abdabc", "abcab", "ab", "xyz", ""] {
+            assert!(matches_prepared(input, &dfa) == matches_prepared(input, &cloned));
+        }
+    }
+
+    #[test]
+    fn transition_map_matches_transitions() {
+        let dfa = exactly("abc").or("abd").repeat_forever(1).prepare_to_match();
+        let map = dfa.to_transition_map();
+
+        assert!(map.len() == dfa.count_states() as usize);
+
+        for state in 0..dfa.count_states() {
+            assert!(map[&state] == dfa.get_transitions_for_state(state));
+        }
+    }
+
+    #[test]
+    fn accepting_states_matches_output_symbols() {
+        let dfa         = exactly("abc").or("abd").repeat_forever(1).prepare_to_match();
+        let accepting   = dfa.accepting_states();
+
+        assert!(accepting.len() > 0);
+
+        for state in 0..dfa.count_states() {
+            assert!(accepting.get(&state) == dfa.output_symbol_for_state(state));
+        }
+    }
+
+    #[test]
+    fn trace_shows_failing_symbol() {
+        let dfa     = exactly("abc").prepare_to_match();
+        let trace   = dfa.trace(&mut "abX".read_symbols());
+
+        assert!(trace == vec![(0, Some('a')), (1, Some('b')), (2, Some('X'))]);
+    }
+
+    #[test]
+    fn trace_ends_at_end_of_input() {
+        let dfa     = exactly("abc").prepare_to_match();
+        let trace   = dfa.trace(&mut "ab".read_symbols());
+
+        assert!(trace == vec![(0, Some('a')), (1, Some('b')), (2, None)]);
+    }
+
+    #[test]
+    fn can_feed_matcher_in_batches() {
+        let dfa         = exactly("abc").prepare_to_match();
+        let mut matcher = dfa.matcher();
+
+        for symbol in "ab".chars() {
+            assert!(matcher.feed(symbol) == FeedResult::Continue);
+        }
+
+        for symbol in "c".chars() {
+            assert!(matcher.feed(symbol) == FeedResult::Continue);
+        }
+
+        assert!(matcher.finish() == Some((3, &())));
+    }
+
+    #[test]
+    fn matcher_completes_on_rejected_symbol() {
+        let dfa         = exactly("ab").repeat_forever(1).prepare_to_match();
+        let mut matcher = dfa.matcher();
+
+        assert!(matcher.feed('a') == FeedResult::Continue);
+        assert!(matcher.feed('b') == FeedResult::Continue);
+        assert!(matcher.feed('x') == FeedResult::Complete(Some((2, &()))));
+
+        // Stays complete
+        assert!(matcher.feed('a') == FeedResult::Complete(Some((2, &()))));
+        assert!(matcher.finish() == Some((2, &())));
+    }
+
+    #[test]
+    fn matcher_can_reject() {
+        let dfa         = exactly("abc").prepare_to_match();
+        let mut matcher = dfa.matcher();
+
+        assert!(matcher.feed('a') == FeedResult::Continue);
+        assert!(matcher.feed('x') == FeedResult::Complete(None));
+        assert!(matcher.finish() == None);
+    }
+
+    #[test]
+    fn matcher_can_use_borrowed_output_symbols() {
+        let output = String::from("abc");
+
+        let mut builder = SymbolRangeDfaBuilder::new();
+        builder.start_state();
+        builder.transition(SymbolRange::new('a', 'a'), 1);
+        builder.start_state();
+        builder.accept(output.as_str());
+
+        let dfa: SymbolRangeDfa<char, &str> = builder.build();
+        let mut matcher = dfa.matcher();
+
+        assert!(matcher.feed('a') == FeedResult::Continue);
+        assert!(matcher.finish() == Some((1, &"abc")));
+    }
+
+    #[test]
+    fn difference_removes_other_language() {
+        let ab_repeated: SymbolRangeDfa<char, ()>   = exactly("ab").repeat_forever(1).prepare_to_match();
+        let abab: SymbolRangeDfa<char, ()>          = exactly("abab").prepare_to_match();
+        let difference                              = ab_repeated.difference(&abab);
+
+        assert!(match_pattern(difference.start(), &mut "ab".read_symbols()).accept_length() == Some(2));
+        assert!(match_pattern(difference.start(), &mut "abab".read_symbols()).accept_length() == Some(2));
+        assert!(match_pattern(difference.start(), &mut "ababab".read_symbols()).accept_length() == Some(6));
+    }
+
+    #[test]
+    fn difference_with_overlapping_ranges() {
+        let letters: SymbolRangeDfa<char, ()>   = MatchRange('a', 'z').prepare_to_match();
+        let vowels: SymbolRangeDfa<char, ()>    = MatchRange('a', 'a').or(MatchRange('e', 'e')).or(MatchRange('x', 'z')).prepare_to_match();
+        let consonants                          = letters.difference(&vowels);
+
+        assert!(match_pattern(consonants.start(), &mut "b".read_symbols()).accept_length() == Some(1));
+        assert!(match_pattern(consonants.start(), &mut "w".read_symbols()).accept_length() == Some(1));
+        assert!(match_pattern(consonants.start(), &mut "a".read_symbols()).is_reject());
+        assert!(match_pattern(consonants.start(), &mut "e".read_symbols()).is_reject());
+        assert!(match_pattern(consonants.start(), &mut "y".read_symbols()).is_reject());
+        assert!(match_pattern(consonants.start(), &mut "z".read_symbols()).is_reject());
     }
 }
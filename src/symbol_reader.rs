@@ -19,11 +19,20 @@
 //!
 
 use std::slice::Iter;
+use std::vec::IntoIter;
 use std::io::Read;
 use std::io::Bytes;
+use std::io;
 use std::str::Chars;
 use std::marker::PhantomData;
 
+#[cfg(feature = "unicode-segmentation")]
+use std::char;
+#[cfg(feature = "unicode-segmentation")]
+use unicode_segmentation::{UnicodeSegmentation, Graphemes};
+#[cfg(feature = "unicode-segmentation")]
+use super::countable::*;
+
 ///
 /// A symbol reader reads one symbol at a time from a source
 ///
@@ -34,6 +43,74 @@ pub trait SymbolReader<Symbol> {
     fn next_symbol(&mut self) -> Option<Symbol>;
 }
 
+///
+/// Trait implemented by symbol readers that may know how many symbols they have left to read
+///
+pub trait SizedSymbolReader<Symbol> : SymbolReader<Symbol> {
+    ///
+    /// Returns the number of symbols remaining in this reader, or `None` if this isn't known
+    ///
+    fn remaining(&self) -> Option<usize>;
+}
+
+///
+/// Trait implemented by symbol readers that can fail while reading symbols
+///
+/// `SymbolReader` can only indicate that a stream has finished, so readers that can encounter errors (such as IO errors) can
+/// implement this trait to make the errors available. Use `into_infallible` to get a `SymbolReader` that passes any errors to
+/// a function and then ends the stream.
+///
+pub trait FallibleSymbolReader<Symbol> : Sized {
+    /// The type of error that can be produced by this reader
+    type Error;
+
+    ///
+    /// Reads the next symbol from this reader, or returns the error that prevented it from being read
+    ///
+    fn try_next_symbol(&mut self) -> Result<Option<Symbol>, Self::Error>;
+
+    ///
+    /// Converts this into a symbol reader that calls `on_error` for any error that occurs and treats it as the end of the stream
+    ///
+    fn into_infallible<ErrorFn: FnMut(Self::Error)>(self, on_error: ErrorFn) -> InfallibleReader<Symbol, Self, ErrorFn> {
+        InfallibleReader { source_stream: self, on_error: on_error, failed: false, symbol: PhantomData }
+    }
+}
+
+///
+/// Symbol reader that reads from a fallible reader, ending the stream at the first error
+///
+pub struct InfallibleReader<Symbol, Reader: FallibleSymbolReader<Symbol>, ErrorFn: FnMut(Reader::Error)> {
+    /// The source stream
+    source_stream: Reader,
+
+    /// The function called when an error occurs
+    on_error: ErrorFn,
+
+    /// True if an error has occurred (no more symbols will be read)
+    failed: bool,
+
+    /// Marker so that the symbol type is used
+    symbol: PhantomData<Symbol>
+}
+
+impl<Symbol, Reader: FallibleSymbolReader<Symbol>, ErrorFn: FnMut(Reader::Error)> SymbolReader<Symbol> for InfallibleReader<Symbol, Reader, ErrorFn> {
+    fn next_symbol(&mut self) -> Option<Symbol> {
+        if self.failed {
+            return None;
+        }
+
+        match self.source_stream.try_next_symbol() {
+            Ok(symbol)  => symbol,
+            Err(error)  => {
+                self.failed = true;
+                (self.on_error)(error);
+                None
+            }
+        }
+    }
+}
+
 ///
 /// Trait that can be implemented by things that can construct a symbol reader
 ///
@@ -53,6 +130,14 @@ impl<'a, Symbol: Clone+'a> SymbolSource<'a, Symbol> for &'a Vec<Symbol> {
     }
 }
 
+impl<'a, Symbol: Clone+'a> SymbolSource<'a, Symbol> for &'a [Symbol] {
+    type SymbolReader = Iter<'a, Symbol>;
+
+    fn read_symbols(self) -> Self::SymbolReader {
+        self.iter()
+    }
+}
+
 impl<'a, Symbol: Clone+'a> SymbolReader<Symbol> for Iter<'a, Symbol> {
     fn next_symbol(&mut self) -> Option<Symbol> {
         if let Some(sym) = self.next() {
@@ -63,6 +148,13 @@ impl<'a, Symbol: Clone+'a> SymbolReader<Symbol> for Iter<'a, Symbol> {
     }
 }
 
+impl<'a, Symbol: Clone+'a> SizedSymbolReader<Symbol> for Iter<'a, Symbol> {
+    #[inline]
+    fn remaining(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
 // TODO: this should really be generalised to work on any Iterator, but using for Iterator<Item=Symbol> doesn't seem to match them
 // Only need FilterMaps for now so just implement it there
 use std::iter::FilterMap;
@@ -135,6 +227,26 @@ impl<Reader: Read> SymbolReader<u8> for ByteSymbolReader<Reader> {
     }
 }
 
+impl<Reader: Read> FallibleSymbolReader<u8> for ByteSymbolReader<Reader> {
+    type Error = io::Error;
+
+    fn try_next_symbol(&mut self) -> Result<Option<u8>, io::Error> {
+        match self.bytes.next() {
+            Some(Ok(sym))   => Ok(Some(sym)),
+            Some(Err(err))  => Err(err),
+            None            => Ok(None)
+        }
+    }
+}
+
+impl<Reader: Read> SizedSymbolReader<u8> for ByteSymbolReader<Reader> {
+    #[inline]
+    fn remaining(&self) -> Option<usize> {
+        // Streams don't know how many bytes they have left
+        None
+    }
+}
+
 //
 // Can read from strings 
 //
@@ -152,6 +264,60 @@ impl<'a> SymbolReader<char> for Chars<'a> {
     }
 }
 
+impl<'a> SymbolSource<'a, char> for &'a String {
+    type SymbolReader = Chars<'a>;
+
+    fn read_symbols(self) -> Self::SymbolReader {
+        self.chars()
+    }
+}
+
+///
+/// Symbol reader that takes ownership of a string and reads its characters
+///
+pub struct StringSymbolReader {
+    /// The string being read
+    string: String,
+
+    /// The byte offset of the next character to read
+    position: usize
+}
+
+impl<'a> SymbolSource<'a, char> for String {
+    type SymbolReader = StringSymbolReader;
+
+    fn read_symbols(self) -> Self::SymbolReader {
+        StringSymbolReader { string: self, position: 0 }
+    }
+}
+
+impl SymbolReader<char> for StringSymbolReader {
+    fn next_symbol(&mut self) -> Option<char> {
+        let next_char = self.string[self.position..].chars().next();
+
+        if let Some(next_char) = next_char {
+            self.position += next_char.len_utf8();
+        }
+
+        next_char
+    }
+}
+
+///
+/// Provides a way to read the UTF-8 bytes of a string as symbols (`read_symbols()` reads its characters)
+///
+pub trait ByteSymbolSource {
+    /// Returns a reader for the bytes in this string
+    fn bytes_symbols<'a>(&'a self) -> Iter<'a, u8>;
+}
+
+impl ByteSymbolSource for str {
+    #[inline]
+    fn bytes_symbols<'a>(&'a self) -> Iter<'a, u8> {
+        self.as_bytes().iter()
+    }
+}
+
 //
 // Boxed symbol readers act like normal symbol readers
 //
@@ -163,12 +329,96 @@ impl<'a, Symbol> SymbolReader<Symbol> for Box<SymbolReader<Symbol>+'a> {
     }
 }
 
+///
+/// Reads the extended grapheme clusters (user-perceived characters) from a string
+///
+/// Some characters are made up of several codepoints (for example, flag emoji or letters followed by combining marks),
+/// so reading these as `char` symbols can split them up. This reader returns each cluster as a single symbol. Use
+/// `map_symbols(Grapheme::from)` to convert these into symbols that can be matched against a pattern.
+///
+#[cfg(feature = "unicode-segmentation")]
+pub struct GraphemeReader<'a> {
+    graphemes: Graphemes<'a>
+}
+
+#[cfg(feature = "unicode-segmentation")]
+impl<'a> GraphemeReader<'a> {
+    ///
+    /// Creates a new grapheme reader that will read from a string
+    ///
+    pub fn new(text: &'a str) -> GraphemeReader<'a> {
+        GraphemeReader { graphemes: text.graphemes(true) }
+    }
+}
+
+#[cfg(feature = "unicode-segmentation")]
+impl<'a> SymbolReader<&'a str> for GraphemeReader<'a> {
+    #[inline]
+    fn next_symbol(&mut self) -> Option<&'a str> {
+        self.graphemes.next()
+    }
+}
+
+///
+/// A grapheme cluster that can be used as a symbol in a pattern
+///
+/// Graphemes are ordered the same way as strings.
+///
+#[cfg(feature = "unicode-segmentation")]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Grapheme(pub String);
+
+#[cfg(feature = "unicode-segmentation")]
+impl<'a> From<&'a str> for Grapheme {
+    fn from(grapheme: &'a str) -> Grapheme {
+        Grapheme(grapheme.to_string())
+    }
+}
+
+///
+/// The string immediately after any other string is found by adding a `'\0'` to the end of it. Strings don't have an
+/// immediately preceding value, so `prev()` returns the closest string ending in `char::MAX` instead. This only affects
+/// ranges whose endpoints are followed by strings made up of many `char::MAX` characters, which should never appear in
+/// real text.
+///
+#[cfg(feature = "unicode-segmentation")]
+impl Countable for Grapheme {
+    fn next(&self) -> Self {
+        let mut next = self.0.clone();
+        next.push('\0');
+
+        Grapheme(next)
+    }
+
+    fn prev(&self) -> Self {
+        let mut prev = self.0.clone();
+
+        match prev.pop() {
+            None        => { },
+            Some('\0')  => { },
+            Some(last)  => {
+                // Skip over the surrogate range when moving to the previous character
+                let last_code   = last as u32;
+                let prev_code   = if last_code == 0xe000 { 0xd7ff } else { last_code-1 };
+
+                prev.push(char::from_u32(prev_code).unwrap());
+                prev.push(char::MAX);
+            }
+        }
+
+        Grapheme(prev)
+    }
+}
+
 ///
 /// Converts symbol streams to vectors
 ///
 pub trait ReaderToVector<Symbol> {
     /// Reads every symbol in this object and returns a vector
     fn to_vec(&mut self) -> Vec<Symbol>;
+
+    /// Clears a buffer and then fills it with every symbol in this object, so its allocation can be reused
+    fn drain_into(&mut self, buffer: &mut Vec<Symbol>);
 }
 
 impl<Symbol, Reader: SymbolReader<Symbol>> ReaderToVector<Symbol> for Reader {
@@ -182,6 +432,15 @@ impl<Symbol, Reader: SymbolReader<Symbol>> ReaderToVector<Symbol> for Reader {
 
         result
     }
+
+    #[inline]
+    fn drain_into(&mut self, buffer: &mut Vec<Symbol>) {
+        buffer.clear();
+
+        while let Some(symbol) = self.next_symbol() {
+            buffer.push(symbol);
+        }
+    }
 }
 
 ///
@@ -235,6 +494,425 @@ where MapFunction: FnMut(InputSymbol) -> OutputSymbol {
     }
 }
 
+///
+/// A symbol stream that transforms the symbols from a source stream using a function that can update some state
+///
+pub struct ScannedStream<Symbol, State, ScanFunction, Reader: SymbolReader<Symbol>> {
+    /// The source stream
+    source_stream: Reader,
+
+    /// The state passed to the scan function
+    state: State,
+
+    /// Function that generates the output symbols
+    scan_function: ScanFunction,
+
+    /// Marker so that the symbol type is used
+    symbol: PhantomData<Symbol>
+}
+
+///
+/// Provides a way to transform symbol streams while keeping track of some state
+///
+pub trait ScanSymbolReader<Symbol> : SymbolReader<Symbol>+Sized {
+    ///
+    /// Transforms the symbols in this stream using a function that can update a state value
+    ///
+    /// The function is called with the state and each input symbol in turn. It can return `Some(symbol)` to produce an output
+    /// symbol or `None` to consume the input symbol without producing anything, so several input symbols can be combined
+    /// into one output symbol. Any state that's still waiting when the source stream ends is discarded.
+    ///
+    fn scan_symbols<State, OutputSymbol, ScanFunction>(self, initial_state: State, scan_function: ScanFunction) -> ScannedStream<Symbol, State, ScanFunction, Self>
+    where ScanFunction: FnMut(&mut State, Symbol) -> Option<OutputSymbol>;
+}
+
+impl<Symbol, Reader: SymbolReader<Symbol>> ScanSymbolReader<Symbol> for Reader {
+    fn scan_symbols<State, OutputSymbol, ScanFunction>(self, initial_state: State, scan_function: ScanFunction) -> ScannedStream<Symbol, State, ScanFunction, Self>
+    where ScanFunction: FnMut(&mut State, Symbol) -> Option<OutputSymbol> {
+        ScannedStream {
+            source_stream:  self,
+            state:          initial_state,
+            scan_function:  scan_function,
+            symbol:         PhantomData
+        }
+    }
+}
+
+impl<Symbol, State, OutputSymbol, ScanFunction, Reader: SymbolReader<Symbol>> SymbolReader<OutputSymbol> for ScannedStream<Symbol, State, ScanFunction, Reader>
+where ScanFunction: FnMut(&mut State, Symbol) -> Option<OutputSymbol> {
+    fn next_symbol(&mut self) -> Option<OutputSymbol> {
+        // Keep reading until the scan function produces a symbol or the source stream runs out
+        while let Some(input_symbol) = self.source_stream.next_symbol() {
+            if let Some(output_symbol) = (self.scan_function)(&mut self.state, input_symbol) {
+                return Some(output_symbol);
+            }
+        }
+
+        None
+    }
+}
+
+///
+/// A symbol stream that reads pairs of symbols from two source streams in lockstep
+///
+pub struct ZippedStream<FirstSymbol, SecondSymbol, FirstReader: SymbolReader<FirstSymbol>, SecondReader: SymbolReader<SecondSymbol>> {
+    /// The stream that supplies the first symbol of each pair
+    first_stream: FirstReader,
+
+    /// The stream that supplies the second symbol of each pair
+    second_stream: SecondReader,
+
+    /// Marker so that the symbol types are used
+    symbols: PhantomData<(FirstSymbol, SecondSymbol)>
+}
+
+///
+/// Provides a way to combine two symbol streams into a stream of pairs
+///
+pub trait ZipSymbolReader<Symbol> : SymbolReader<Symbol>+Sized {
+    ///
+    /// Reads symbols from this stream and another in lockstep, producing pairs until either stream ends
+    ///
+    /// This is called `zip_symbols` rather than `zip` so that it doesn't conflict with `Iterator::zip` for readers that are
+    /// also iterators.
+    ///
+    fn zip_symbols<OtherSymbol, OtherReader: SymbolReader<OtherSymbol>>(self, other: OtherReader) -> ZippedStream<Symbol, OtherSymbol, Self, OtherReader>;
+}
+
+impl<Symbol, Reader: SymbolReader<Symbol>> ZipSymbolReader<Symbol> for Reader {
+    fn zip_symbols<OtherSymbol, OtherReader: SymbolReader<OtherSymbol>>(self, other: OtherReader) -> ZippedStream<Symbol, OtherSymbol, Self, OtherReader> {
+        ZippedStream {
+            first_stream:   self,
+            second_stream:  other,
+            symbols:        PhantomData
+        }
+    }
+}
+
+impl<FirstSymbol, SecondSymbol, FirstReader: SymbolReader<FirstSymbol>, SecondReader: SymbolReader<SecondSymbol>> SymbolReader<(FirstSymbol, SecondSymbol)> for ZippedStream<FirstSymbol, SecondSymbol, FirstReader, SecondReader> {
+    fn next_symbol(&mut self) -> Option<(FirstSymbol, SecondSymbol)> {
+        let first   = self.first_stream.next_symbol()?;
+        let second  = self.second_stream.next_symbol()?;
+
+        Some((first, second))
+    }
+}
+
+///
+/// A symbol stream that combines runs of identical symbols from a source stream into `(symbol, count)` pairs
+///
+pub struct GroupedRuns<Symbol, Reader: SymbolReader<Symbol>> {
+    /// The source stream
+    source_stream: Reader,
+
+    /// The symbol that was read from the source stream after the end of the last run
+    next: Option<Symbol>
+}
+
+///
+/// Provides a way to group runs of identical symbols in a stream
+///
+pub trait GroupRunsSymbolReader<Symbol: PartialEq+Clone> : SymbolReader<Symbol>+Sized {
+    /// Produces a stream of the runs of identical symbols in this stream, along with the length of each run
+    fn group_runs(self) -> GroupedRuns<Symbol, Self>;
+}
+
+impl<Symbol: PartialEq+Clone, Reader: SymbolReader<Symbol>> GroupRunsSymbolReader<Symbol> for Reader {
+    fn group_runs(self) -> GroupedRuns<Symbol, Self> {
+        GroupedRuns {
+            source_stream:  self,
+            next:           None
+        }
+    }
+}
+
+impl<Symbol: PartialEq+Clone, Reader: SymbolReader<Symbol>> SymbolReader<(Symbol, usize)> for GroupedRuns<Symbol, Reader> {
+    fn next_symbol(&mut self) -> Option<(Symbol, usize)> {
+        // The run starts with the symbol left over from the previous run, or the next symbol in the stream
+        let run_symbol = match self.next.take() {
+            Some(symbol)    => symbol,
+            None            => self.source_stream.next_symbol()?
+        };
+
+        // Count symbols until we find one that's different
+        let mut count = 1;
+
+        while let Some(symbol) = self.source_stream.next_symbol() {
+            if symbol == run_symbol {
+                count += 1;
+            } else {
+                self.next = Some(symbol);
+                break;
+            }
+        }
+
+        Some((run_symbol, count))
+    }
+}
+
+///
+/// A symbol stream that drops any symbol that is the same as the symbol immediately before it
+///
+pub struct DedupConsecutive<Symbol, Reader: SymbolReader<Symbol>> {
+    /// The source stream
+    source_stream: Reader,
+
+    /// The last symbol that was produced by this stream
+    last: Option<Symbol>
+}
+
+///
+/// Provides a way to remove consecutive duplicate symbols from a stream
+///
+pub trait DedupSymbolReader<Symbol: PartialEq+Clone> : SymbolReader<Symbol>+Sized {
+    /// Produces a stream where each run of identical symbols in this stream is collapsed into a single symbol
+    fn dedup_consecutive(self) -> DedupConsecutive<Symbol, Self>;
+}
+
+impl<Symbol: PartialEq+Clone, Reader: SymbolReader<Symbol>> DedupSymbolReader<Symbol> for Reader {
+    fn dedup_consecutive(self) -> DedupConsecutive<Symbol, Self> {
+        DedupConsecutive {
+            source_stream:  self,
+            last:           None
+        }
+    }
+}
+
+impl<Symbol: PartialEq+Clone, Reader: SymbolReader<Symbol>> SymbolReader<Symbol> for DedupConsecutive<Symbol, Reader> {
+    fn next_symbol(&mut self) -> Option<Symbol> {
+        loop {
+            let symbol = self.source_stream.next_symbol()?;
+
+            // Skip over symbols that repeat the previous one
+            if self.last.as_ref() != Some(&symbol) {
+                self.last = Some(symbol.clone());
+                return Some(symbol);
+            }
+        }
+    }
+}
+
+///
+/// A symbol stream that batches the symbols from a source stream into fixed-size chunks
+///
+pub struct Chunks<Symbol, Reader: SymbolReader<Symbol>> {
+    /// The source stream
+    source_stream: Reader,
+
+    /// The maximum number of symbols in each chunk
+    size: usize,
+
+    /// Marker so that the symbol type is used
+    symbol: PhantomData<Symbol>
+}
+
+///
+/// Provides a way to read a stream in fixed-size chunks
+///
+pub trait ChunkSymbolReader<Symbol> : SymbolReader<Symbol>+Sized {
+    ///
+    /// Produces a stream of vectors containing up to `size` symbols each from this stream
+    ///
+    /// Every chunk except the last one will contain exactly `size` symbols. The size must be greater than 0.
+    ///
+    fn chunks(self, size: usize) -> Chunks<Symbol, Self>;
+}
+
+impl<Symbol, Reader: SymbolReader<Symbol>> ChunkSymbolReader<Symbol> for Reader {
+    fn chunks(self, size: usize) -> Chunks<Symbol, Self> {
+        if size == 0 {
+            panic!("Chunks must contain at least one symbol");
+        }
+
+        Chunks {
+            source_stream:  self,
+            size:           size,
+            symbol:         PhantomData
+        }
+    }
+}
+
+impl<Symbol, Reader: SymbolReader<Symbol>> SymbolReader<Vec<Symbol>> for Chunks<Symbol, Reader> {
+    fn next_symbol(&mut self) -> Option<Vec<Symbol>> {
+        let mut chunk = Vec::with_capacity(self.size);
+
+        while chunk.len() < self.size {
+            match self.source_stream.next_symbol() {
+                Some(symbol)    => chunk.push(symbol),
+                None            => break
+            }
+        }
+
+        if chunk.is_empty() {
+            None
+        } else {
+            Some(chunk)
+        }
+    }
+}
+
+///
+/// A symbol stream that splits a stream of bytes into frames, each made up of a header containing its length followed by a payload
+///
+pub struct LengthFramedReader<Reader: SymbolReader<u8>, LengthFunction: Fn(&[u8]) -> Option<usize>> {
+    /// The source stream
+    source_stream: Reader,
+
+    /// Function that returns the payload length once enough of the header has been read
+    length_of: LengthFunction
+}
+
+///
+/// Provides a way to split byte streams into length-prefixed frames
+///
+pub trait FrameSymbolReader : SymbolReader<u8>+Sized {
+    ///
+    /// Splits this stream into frames, producing the payload of each frame as a symbol
+    ///
+    /// `length_of` is called with the header bytes that have been read so far for each frame. It should return `None` if more
+    /// header bytes are needed, or the length of the payload that follows the header. The stream ends if there is incomplete
+    /// data at the end of the source stream.
+    ///
+    fn frame_by_length<LengthFunction: Fn(&[u8]) -> Option<usize>>(self, length_of: LengthFunction) -> LengthFramedReader<Self, LengthFunction>;
+}
+
+impl<Reader: SymbolReader<u8>> FrameSymbolReader for Reader {
+    fn frame_by_length<LengthFunction: Fn(&[u8]) -> Option<usize>>(self, length_of: LengthFunction) -> LengthFramedReader<Self, LengthFunction> {
+        LengthFramedReader {
+            source_stream:  self,
+            length_of:      length_of
+        }
+    }
+}
+
+impl<Reader: SymbolReader<u8>, LengthFunction: Fn(&[u8]) -> Option<usize>> SymbolReader<Vec<u8>> for LengthFramedReader<Reader, LengthFunction> {
+    fn next_symbol(&mut self) -> Option<Vec<u8>> {
+        // Read the header until we know how long the payload is
+        let mut header = vec![];
+
+        let length = loop {
+            if let Some(length) = (self.length_of)(&header) {
+                break length;
+            }
+
+            header.push(self.source_stream.next_symbol()?);
+        };
+
+        // Read the payload
+        let mut payload = Vec::with_capacity(length);
+
+        while payload.len() < length {
+            payload.push(self.source_stream.next_symbol()?);
+        }
+
+        Some(payload)
+    }
+}
+
+///
+/// An iterator that reads its items from a symbol reader
+///
+pub struct SymbolReaderIter<Symbol, Reader: SymbolReader<Symbol>> {
+    /// The source stream
+    source_stream: Reader,
+
+    /// Marker so that the symbol type is used
+    symbol: PhantomData<Symbol>
+}
+
+///
+/// Provides a way to use a symbol reader as a standard iterator
+///
+/// This is called `into_symbol_iter` rather than `into_iter` as some readers (such as `Chars`) are already iterators, which
+/// would make calls to `IntoIterator::into_iter` ambiguous for them.
+///
+pub trait IterSymbolReader<Symbol> : SymbolReader<Symbol>+Sized {
+    ///
+    /// Converts this stream into an iterator, so the standard iterator combinators can be used with it
+    ///
+    fn into_symbol_iter(self) -> SymbolReaderIter<Symbol, Self>;
+}
+
+impl<Symbol, Reader: SymbolReader<Symbol>> IterSymbolReader<Symbol> for Reader {
+    fn into_symbol_iter(self) -> SymbolReaderIter<Symbol, Self> {
+        SymbolReaderIter {
+            source_stream:  self,
+            symbol:         PhantomData
+        }
+    }
+}
+
+impl<Symbol, Reader: SymbolReader<Symbol>> Iterator for SymbolReaderIter<Symbol, Reader> {
+    type Item = Symbol;
+
+    #[inline]
+    fn next(&mut self) -> Option<Symbol> {
+        self.source_stream.next_symbol()
+    }
+}
+
+///
+/// A symbol reader that reads its symbols from any iterator
+///
+pub struct IteratorReader<Source: Iterator> {
+    iterator: Source
+}
+
+impl<Source: Iterator> IteratorReader<Source> {
+    ///
+    /// Creates a new symbol reader that reads the items from an iterator
+    ///
+    pub fn from_iter(iterator: Source) -> IteratorReader<Source> {
+        IteratorReader { iterator: iterator }
+    }
+}
+
+impl<Source: Iterator> SymbolReader<Source::Item> for IteratorReader<Source> {
+    #[inline]
+    fn next_symbol(&mut self) -> Option<Source::Item> {
+        self.iterator.next()
+    }
+}
+
+///
+/// A symbol reader that reads from an iterator that produces batches of symbols
+///
+/// This is useful when symbols arrive in chunks (for example, from a channel fed by another thread). The next batch is only
+/// requested once all of the symbols from the current batch have been read, and empty batches are skipped.
+///
+pub struct BatchReader<Symbol, Batches: Iterator<Item=Vec<Symbol>>> {
+    /// The source of the batches
+    batches: Batches,
+
+    /// The symbols remaining in the current batch
+    current_batch: IntoIter<Symbol>
+}
+
+impl<Symbol, Batches: Iterator<Item=Vec<Symbol>>> BatchReader<Symbol, Batches> {
+    ///
+    /// Creates a new reader that reads the symbols from each batch in turn
+    ///
+    pub fn new(batches: Batches) -> BatchReader<Symbol, Batches> {
+        BatchReader {
+            batches:        batches,
+            current_batch:  vec![].into_iter()
+        }
+    }
+}
+
+impl<Symbol, Batches: Iterator<Item=Vec<Symbol>>> SymbolReader<Symbol> for BatchReader<Symbol, Batches> {
+    fn next_symbol(&mut self) -> Option<Symbol> {
+        loop {
+            if let Some(symbol) = self.current_batch.next() {
+                return Some(symbol);
+            }
+
+            // Move on to the next batch, finishing when there are no more
+            self.current_batch = self.batches.next()?.into_iter();
+        }
+    }
+}
+
 ///
 /// A VecReader consumes a vector, which can be read out using the SymbolReader trait
 ///
@@ -259,6 +937,13 @@ impl<Symbol> SymbolReader<Symbol> for VecReader<Symbol> {
     }
 }
 
+impl<Symbol> SizedSymbolReader<Symbol> for VecReader<Symbol> {
+    #[inline]
+    fn remaining(&self) -> Option<usize> {
+        Some(self.symbols.len())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -303,6 +988,275 @@ mod test {
         assert!(result == vec![2, 3, 4]);
     }
 
+    #[test]
+    fn can_group_runs() {
+        let result = "aaabbc".read_symbols().group_runs().to_vec();
+
+        assert!(result == vec![('a', 3), ('b', 2), ('c', 1)]);
+    }
+
+    #[test]
+    fn can_group_runs_in_empty_stream() {
+        let result = "".read_symbols().group_runs().to_vec();
+
+        assert!(result == vec![]);
+    }
+
+    #[test]
+    fn can_dedup_consecutive_symbols() {
+        let result = "aabbbc".read_symbols().dedup_consecutive().to_vec();
+
+        assert!(result == vec!['a', 'b', 'c']);
+    }
+
+    #[test]
+    fn dedup_keeps_symbols_that_repeat_later() {
+        let result = "aabaa".read_symbols().dedup_consecutive().to_vec();
+
+        assert!(result == vec!['a', 'b', 'a']);
+    }
+
+    #[test]
+    fn can_read_in_chunks() {
+        let source = vec![1, 2, 3, 4, 5];
+        let result = source.read_symbols().chunks(2).to_vec();
+
+        assert!(result == vec![vec![1, 2], vec![3, 4], vec![5]]);
+    }
+
+    #[test]
+    fn chunks_of_empty_stream_are_empty() {
+        let result = "".read_symbols().chunks(3).to_vec();
+
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn can_collapse_escape_sequences() {
+        let result = "a\\nb\\\\c".read_symbols().scan_symbols(false, |escaped, c| {
+            if *escaped {
+                *escaped = false;
+                Some(if c == 'n' { '\n' } else { c })
+            } else if c == '\\' {
+                *escaped = true;
+                None
+            } else {
+                Some(c)
+            }
+        }).to_vec();
+
+        assert!(result == vec!['a', '\n', 'b', '\\', 'c']);
+    }
+
+    #[test]
+    fn can_read_batches() {
+        let batches = vec![vec![1, 2], vec![], vec![3]];
+        let result  = BatchReader::new(batches.into_iter()).to_vec();
+
+        assert!(result == vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn batch_reader_skips_empty_batches() {
+        let batches: Vec<Vec<u8>>   = vec![vec![], vec![], vec![]];
+        let mut reader              = BatchReader::new(batches.into_iter());
+
+        assert!(reader.next_symbol() == None);
+        assert!(reader.next_symbol() == None);
+    }
+
+    #[test]
+    fn batch_reader_requests_batches_on_demand() {
+        use std::cell::Cell;
+
+        let requested   = Cell::new(0);
+        let batches     = (0..3).map(|batch| { requested.set(requested.get()+1); vec![batch; 2] });
+        let mut reader  = BatchReader::new(batches);
+
+        assert!(reader.next_symbol() == Some(0));
+        assert!(requested.get() == 1);
+        assert!(reader.next_symbol() == Some(0));
+        assert!(requested.get() == 1);
+        assert!(reader.next_symbol() == Some(1));
+        assert!(requested.get() == 2);
+    }
+
+    #[test]
+    fn can_round_trip_through_iterators() {
+        let source  = vec![1, 2, 3];
+        let doubled = VecReader::from_vec(source).into_symbol_iter().map(|x| x*2);
+        let result  = IteratorReader::from_iter(doubled).to_vec();
+
+        assert!(result == vec![2, 4, 6]);
+    }
+
+    #[test]
+    fn can_use_iterator_combinators_on_reader() {
+        let reader  = IteratorReader::from_iter(vec![1, 2, 3].into_iter());
+        let total   = reader.into_symbol_iter().filter(|x| x % 2 == 1).sum::<i32>();
+
+        assert!(total == 4);
+    }
+
+    ///
+    /// Reader that produces some bytes and then fails
+    ///
+    struct FailingReader {
+        remaining: Vec<u8>
+    }
+
+    impl ::std::io::Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+            if self.remaining.is_empty() {
+                Err(::std::io::Error::new(::std::io::ErrorKind::Other, "Failed"))
+            } else {
+                buf[0] = self.remaining.remove(0);
+                Ok(1)
+            }
+        }
+    }
+
+    #[test]
+    fn fallible_reader_reports_errors() {
+        let mut reader = ByteSymbolReader::from(FailingReader { remaining: vec![1, 2] });
+
+        assert!(reader.try_next_symbol().unwrap() == Some(1));
+        assert!(reader.try_next_symbol().unwrap() == Some(2));
+        assert!(reader.try_next_symbol().is_err());
+    }
+
+    #[test]
+    fn infallible_reader_passes_errors_to_function() {
+        let mut errors  = vec![];
+        let result      = ByteSymbolReader::from(FailingReader { remaining: vec![1, 2] })
+            .into_infallible(|error| errors.push(error.to_string()))
+            .to_vec();
+
+        assert!(result == vec![1, 2]);
+        assert!(errors == vec!["Failed".to_string()]);
+    }
+
+    #[test]
+    fn vecreader_knows_remaining_symbols() {
+        let mut reader = VecReader::from_vec(vec![1, 2, 3, 4, 5]);
+
+        assert!(reader.remaining() == Some(5));
+        reader.next_symbol();
+        assert!(reader.remaining() == Some(4));
+        reader.to_vec();
+        assert!(reader.remaining() == Some(0));
+    }
+
+    #[test]
+    fn slice_reader_knows_remaining_symbols() {
+        let source      = vec![1, 2, 3];
+        let mut reader  = source.read_symbols();
+
+        assert!(reader.remaining() == Some(3));
+        reader.next_symbol();
+        assert!(reader.remaining() == Some(2));
+    }
+
+    #[test]
+    fn bytes_reader_does_not_know_remaining_symbols() {
+        let array: [u8; 3] = [1, 2, 3];
+        let reader = ByteSymbolReader::from(&array[..]);
+
+        assert!(reader.remaining() == None);
+    }
+
+    #[test]
+    fn can_read_from_slice() {
+        let source: &[u8]   = &[1, 2, 3];
+        let result          = source.read_symbols().to_vec();
+
+        assert!(result == vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn zip_stops_at_shorter_stream() {
+        let numbers = vec![1, 2, 3];
+        let letters = vec!['a', 'b'];
+
+        let zipped = numbers.read_symbols().zip_symbols(letters.read_symbols()).to_vec();
+
+        assert!(zipped == vec![(1, 'a'), (2, 'b')]);
+    }
+
+    #[test]
+    fn zip_with_empty_stream_is_empty() {
+        let numbers: Vec<u32> = vec![];
+
+        assert!("abc".read_symbols().zip_symbols(numbers.read_symbols()).to_vec() == vec![]);
+    }
+
+    #[test]
+    fn drain_into_matches_to_vec() {
+        let source      = vec![1, 2, 3];
+        let mut buffer  = vec![];
+
+        source.read_symbols().drain_into(&mut buffer);
+
+        assert!(buffer == source.read_symbols().to_vec());
+    }
+
+    #[test]
+    fn drain_into_overwrites_buffer() {
+        let mut buffer = vec![];
+
+        vec![1, 2, 3, 4].read_symbols().drain_into(&mut buffer);
+        vec![5, 6].read_symbols().drain_into(&mut buffer);
+
+        assert!(buffer == vec![5, 6]);
+    }
+
+    #[test]
+    fn can_read_owned_string() {
+        let mut reader = String::from("a\u{e9}b").read_symbols();
+
+        assert!(reader.next_symbol() == Some('a'));
+        assert!(reader.next_symbol() == Some('\u{e9}'));
+        assert!(reader.next_symbol() == Some('b'));
+        assert!(reader.next_symbol() == None);
+    }
+
+    #[test]
+    fn can_read_string_as_bytes() {
+        let result = "\u{e9}".bytes_symbols().to_vec();
+
+        assert!(result == vec![0xc3, 0xa9]);
+    }
+
+    #[test]
+    fn can_match_string_bytes() {
+        use super::super::*;
+
+        let pattern = byte_literal(&[0xc3, 0xa9]).repeat_forever(1);
+
+        assert!(matches_reader(&mut "\u{e9}\u{e9}".bytes_symbols(), pattern.clone()) == Some(4));
+        assert!(matches("\u{e9}".as_bytes(), pattern) == Some(2));
+    }
+
+    #[test]
+    fn can_read_length_prefixed_frames() {
+        let source: Vec<u8> = vec![2, 10, 11, 0, 3, 20, 21, 22];
+        let frames          = source.read_symbols()
+            .frame_by_length(|header| header.first().map(|length| *length as usize))
+            .to_vec();
+
+        assert!(frames == vec![vec![10, 11], vec![], vec![20, 21, 22]]);
+    }
+
+    #[test]
+    fn incomplete_frame_ends_stream() {
+        let source: Vec<u8> = vec![2, 10, 11, 3, 20];
+        let mut reader      = source.read_symbols()
+            .frame_by_length(|header| header.first().map(|length| *length as usize));
+
+        assert!(reader.next_symbol() == Some(vec![10, 11]));
+        assert!(reader.next_symbol() == None);
+    }
+
     #[test]
     fn can_read_from_bytes_reader() {
         let array: [u8; 3] = [1, 2, 3];
@@ -324,4 +1278,49 @@ mod test {
         assert!(reader.next_symbol() == Some('c'));
         assert!(reader.next_symbol() == None);
     }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn flag_is_one_grapheme() {
+        let mut reader = GraphemeReader::new("a\u{1F1EC}\u{1F1E7}b");
+
+        assert!(reader.next_symbol() == Some("a"));
+        assert!(reader.next_symbol() == Some("\u{1F1EC}\u{1F1E7}"));
+        assert!(reader.next_symbol() == Some("b"));
+        assert!(reader.next_symbol() == None);
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn can_tokenize_graphemes() {
+        use super::super::*;
+
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+        enum TestToken {
+            Flag,
+            Letter
+        }
+
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(vec![Grapheme::from("\u{1F1EC}\u{1F1E7}")], TestToken::Flag);
+        token_matcher.add_pattern(MatchRange(Grapheme::from("a"), Grapheme::from("z")), TestToken::Letter);
+
+        // The flag is made up of two regional indicator codepoints, and the 'e' is followed by a combining acute accent
+        let reader      = GraphemeReader::new("x\u{1F1EC}\u{1F1E7}e\u{301}").map_symbols(Grapheme::from);
+        let tokenizer   = Tokenizer::new(reader, &token_matcher);
+        let tokens: Vec<_> = tokenizer.collect();
+
+        assert!(tokens == vec![(0..1, TestToken::Letter), (1..2, TestToken::Flag), (2..3, TestToken::Letter)]);
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn grapheme_next_and_prev_surround_grapheme() {
+        let grapheme = Grapheme::from("b");
+
+        assert!(grapheme.next() > grapheme);
+        assert!(grapheme.prev() < grapheme);
+        assert!(grapheme.prev() > Grapheme::from("a\u{301}"));
+        assert!(grapheme.next().prev() == grapheme);
+    }
 }
@@ -95,6 +95,13 @@ impl<Base: Ord+Clone, Tag: Ord+Clone+'static> TaggedStream<Base, Tag> {
         self.data.len()
     }
 
+    ///
+    /// Iterates over the symbols at the top level of this stream
+    ///
+    pub fn iter<'a>(&'a self) -> Iter<'a, TagSymbol<Base, Tag>> {
+        self.data.iter()
+    }
+
     ///
     /// Replaces a range in this stream with a tag
     ///
@@ -206,6 +213,15 @@ impl<Base: Clone+Ord, Tag: Clone+Ord> Index<usize> for TaggedStream<Base, Tag> {
     }
 }
 
+impl<'a, Base: Clone+Ord, Tag: Clone+Ord> IntoIterator for &'a TaggedStream<Base, Tag> {
+    type Item       = &'a TagSymbol<Base, Tag>;
+    type IntoIter   = Iter<'a, TagSymbol<Base, Tag>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.data.iter()
+    }
+}
+
 impl<'a, Base: Clone+Ord, Tag: Clone+Ord> SymbolSource<'a, TagSymbol<Base, Tag>> for &'a TaggedStream<Base, Tag> {
     type SymbolReader = Iter<'a, TagSymbol<Base, Tag>>;
 
@@ -260,6 +276,41 @@ mod test {
         }
     }
 
+    #[test]
+    fn can_iterate_over_tags() {
+        #[derive(Clone, PartialEq, Eq, Copy, PartialOrd, Ord, Debug)]
+        enum Tags {
+            Hello,
+            World
+        }
+
+        let tagged: TaggedStream<char, Tags> = TaggedStream::from_reader(&mut "Hello, World".read_symbols());
+        let tagged = tagged.with_tags(vec![(0..5, Tags::Hello), (7..12, Tags::World)].into_iter());
+
+        let tags: Vec<_> = tagged.iter()
+            .filter_map(|symbol| match symbol {
+                &Tagged(ref tag, _) => Some(*tag),
+                &Untagged(_)        => None
+            })
+            .collect();
+
+        assert!(tags == vec![Tags::Hello, Tags::World]);
+    }
+
+    #[test]
+    fn can_iterate_over_stream_in_for_loop() {
+        let tagged: TaggedStream<char, u32> = TaggedStream::from_reader(&mut "abc".read_symbols());
+        let mut symbols = vec![];
+
+        for symbol in &tagged {
+            if let &Untagged(c) = symbol {
+                symbols.push(c);
+            }
+        }
+
+        assert!(symbols == vec!['a', 'b', 'c']);
+    }
+
     #[test]
     fn can_tag_everything_with_tags() {
         #[derive(Clone, PartialEq, Eq, Copy, PartialOrd, Ord)]
@@ -115,7 +115,7 @@ impl<Symbol: Clone+Sized, SourceReader: SymbolReader<Symbol>> Tape<Symbol, Sourc
     /// Returns the number of symbols in the buffer
     ///
     #[inline]
-    pub fn buffer_size(&mut self) -> usize {
+    pub fn buffer_size(&self) -> usize {
         if self.first_symbol_index > self.last_symbol_index {
             (self.last_symbol_index + self.buffer.len()) - self.first_symbol_index
         } else {
@@ -123,6 +123,36 @@ impl<Symbol: Clone+Sized, SourceReader: SymbolReader<Symbol>> Tape<Symbol, Sourc
         }
     }
 
+    ///
+    /// Returns the number of symbols that are held in the buffer since the last cut (the same as `buffer_size`)
+    ///
+    #[inline]
+    pub fn rewindable_count(&self) -> usize {
+        self.buffer_size()
+    }
+
+    ///
+    /// Returns a copy of the symbols that are held in the buffer, in the order they were read from the source
+    ///
+    /// This starts at the last point where the tape was cut, and includes any symbols that have been rewound over. It's
+    /// mainly useful for inspecting the state of the tape while debugging.
+    ///
+    pub fn buffered_symbols(&self) -> Vec<Symbol> {
+        let mut result      = Vec::with_capacity(self.buffer_size());
+        let mut copy_index  = self.first_symbol_index;
+
+        while copy_index != self.last_symbol_index {
+            if let Some(ref symbol) = self.buffer[copy_index] {
+                result.push(symbol.clone());
+            }
+
+            copy_index += 1;
+            if copy_index >= self.buffer.len() { copy_index = 0; }
+        }
+
+        result
+    }
+
     ///
     /// Moves backwards by num_symbols
     ///
@@ -151,6 +181,32 @@ impl<Symbol: Clone+Sized, SourceReader: SymbolReader<Symbol>> Tape<Symbol, Sourc
     pub fn at_end_of_reader(&self) -> bool {
         self.end_of_reader && self.read_index == self.last_symbol_index
     }
+
+    ///
+    /// Retrieves the number of symbols that have been read from the underlying reader
+    ///
+    /// This differs from `get_source_position` when the tape has been rewound: the symbols after the current position
+    /// have been read from the source reader but are still waiting in the buffer to be read from the tape.
+    ///
+    pub fn absolute_position(&self) -> usize {
+        let buffered_ahead = if self.read_index > self.last_symbol_index {
+            (self.last_symbol_index + self.buffer.len()) - self.read_index
+        } else {
+            self.last_symbol_index - self.read_index
+        };
+
+        self.source_position + buffered_ahead
+    }
+
+    ///
+    /// Consumes this tape and returns the reader it was reading from
+    ///
+    /// Any symbols that have been read from the source but not from the tape (ie, any symbols that have been rewound over)
+    /// are lost: the returned reader will continue from `absolute_position()` rather than `get_source_position()`.
+    ///
+    pub fn into_inner(self) -> SourceReader {
+        self.read_from
+    }
 }
 
 impl<Symbol: Clone+Sized, Reader: SymbolReader<Symbol>+Sized> SymbolReader<Symbol> for Tape<Symbol, Reader> {
@@ -246,6 +302,36 @@ mod test {
         assert!(tape.next_symbol() == None);
     }
 
+    #[test]
+    fn absolute_position_includes_rewound_symbols() {
+        let source_vec    = vec![1,2,3,4,5,6];
+        let source_stream = source_vec.read_symbols();
+        let mut tape      = Tape::new(source_stream);
+
+        tape.next_symbol();
+        tape.next_symbol();
+        tape.next_symbol();
+        assert!(tape.absolute_position() == 3);
+
+        tape.rewind(2);
+        assert!(tape.get_source_position() == 1);
+        assert!(tape.absolute_position() == 3);
+    }
+
+    #[test]
+    fn can_recover_reader_from_tape() {
+        let source_vec    = vec![1,2,3,4,5,6];
+        let source_stream = source_vec.read_symbols();
+        let mut tape      = Tape::new(source_stream);
+
+        assert!(tape.next_symbol() == Some(1));
+        assert!(tape.next_symbol() == Some(2));
+        tape.rewind(1);
+
+        let mut reader = tape.into_inner();
+        assert!(reader.to_vec() == vec![3,4,5,6]);
+    }
+
     #[test]
     fn can_cut_tape() {
         let source_vec    = vec![1,2,3,4,5,6,7,8,9];
@@ -282,4 +368,40 @@ mod test {
         assert!(tape.next_symbol() == Some(9));
         assert!(tape.next_symbol() == None);
     }
+
+    #[test]
+    fn can_inspect_buffered_symbols() {
+        let source_vec    = vec![1,2,3,4,5,6,7,8,9];
+        let source_stream = source_vec.read_symbols();
+        let mut tape      = Tape::new(source_stream);
+
+        assert!(tape.buffered_symbols() == vec![]);
+        assert!(tape.rewindable_count() == 0);
+
+        tape.next_symbol();
+        tape.next_symbol();
+        tape.next_symbol();
+        assert!(tape.buffered_symbols() == vec![1, 2, 3]);
+        assert!(tape.rewindable_count() == 3);
+
+        // Rewinding leaves the symbols in the buffer
+        tape.rewind(2);
+        assert!(tape.buffered_symbols() == vec![1, 2, 3]);
+
+        // Cutting removes the symbols before the read position
+        tape.cut();
+        assert!(tape.buffered_symbols() == vec![2, 3]);
+        assert!(tape.rewindable_count() == 2);
+
+        // Reading past the end of the buffer (including when it wraps around or grows) adds to it
+        for _ in 0..5 { tape.next_symbol(); }
+        assert!(tape.buffered_symbols() == vec![2, 3, 4, 5, 6]);
+        assert!(tape.rewindable_count() == 5);
+
+        tape.cut();
+        assert!(tape.buffered_symbols() == vec![]);
+
+        tape.next_symbol();
+        assert!(tape.buffered_symbols() == vec![7]);
+    }
 }
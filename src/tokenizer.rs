@@ -22,6 +22,7 @@
 //!
 
 use std::ops::Range;
+use std::collections::BTreeMap;
 
 use super::countable::*;
 use super::symbol_range::*;
@@ -35,11 +36,29 @@ use super::pattern_matcher::*;
 use super::matches::*;
 use super::tape::*;
 
+///
+/// Positions in the input stream that a pattern can be anchored to
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Anchor {
+    /// The pattern only matches at the very start of the input stream
+    Start,
+
+    /// The pattern only matches if it ends at the very end of the input stream
+    End
+}
+
 ///
 /// Used for generating tokenizing pattern matchers
 ///
 pub struct TokenMatcher<InputSymbol: Clone+Ord+Countable, OutputSymbol: Clone+Ord> {
-    patterns: Vec<(Pattern<InputSymbol>, OutputSymbol)>
+    patterns: Vec<(Pattern<InputSymbol>, OutputSymbol)>,
+
+    /// Patterns that only match at the start or end of the input
+    anchored_patterns: Vec<(Pattern<InputSymbol>, OutputSymbol, Anchor)>,
+
+    /// The output symbol produced for runs of symbols that can't start a token (None if these are left unmatched)
+    error_token: Option<OutputSymbol>
 }
 
 impl<InputSymbol: Clone+Ord+Countable+'static, OutputSymbol: Clone+Ord+'static> TokenMatcher<InputSymbol, OutputSymbol> {
@@ -47,7 +66,7 @@ impl<InputSymbol: Clone+Ord+Countable+'static, OutputSymbol: Clone+Ord+'static>
     /// Creates a new TokenMatcher
     ///
     pub fn new() -> TokenMatcher<InputSymbol, OutputSymbol> {
-        TokenMatcher { patterns: vec![] }
+        TokenMatcher { patterns: vec![], anchored_patterns: vec![], error_token: None }
     }
 
     ///
@@ -57,13 +76,136 @@ impl<InputSymbol: Clone+Ord+Countable+'static, OutputSymbol: Clone+Ord+'static>
         self.patterns.push((pattern.to_pattern(), output));
     }
 
+    ///
+    /// Adds a new pattern that only matches at the start or the end of the input stream
+    ///
+    /// Anchors aren't regular over a stream, so these patterns are left out of the DFA generated by `prepare_to_match()`
+    /// and are only used by tokenizers created with `Tokenizer::new_anchored()`. A start-anchored pattern is only tried
+    /// when the tokenizer is at position 0, and an end-anchored pattern is only accepted if its match finishes at the
+    /// end of the reader.
+    ///
+    pub fn add_anchored_pattern<TPattern: ToPattern<InputSymbol>>(&mut self, pattern: TPattern, output: OutputSymbol, anchor: Anchor) {
+        self.anchored_patterns.push((pattern.to_pattern(), output, anchor));
+    }
+
+    ///
+    /// Adds all of the patterns from another TokenMatcher to this one
+    ///
+    /// Clashes between the patterns are resolved in the usual way: the lowest output symbol is produced.
+    ///
+    pub fn extend(&mut self, other: TokenMatcher<InputSymbol, OutputSymbol>) {
+        self.patterns.extend(other.patterns);
+        self.anchored_patterns.extend(other.anchored_patterns);
+
+        if self.error_token.is_none() {
+            self.error_token = other.error_token;
+        }
+    }
+
+    ///
+    /// Sets an output symbol to produce for the longest run of input symbols that can't start any of the patterns
+    ///
+    /// Normally a tokenizer stops at input that no pattern matches, and the caller has to skip it one symbol at a time.
+    /// With an error token, the whole run of symbols is matched as a single token instead. This is added to the DFA when
+    /// it is prepared, so it isn't part of the NDFA returned by `to_ndfa()`. A symbol that can start a pattern ends the
+    /// run even if the pattern doesn't go on to match. For tokenizers created with `Tokenizer::new_anchored()`, this
+    /// includes the end-anchored patterns.
+    ///
+    pub fn set_error_token(&mut self, output: OutputSymbol) {
+        self.error_token = Some(output);
+    }
+
+    ///
+    /// Adds the error token (if there is one) to a DFA prepared from this matcher
+    ///
+    fn add_error_token(&self, dfa: SymbolRangeDfa<InputSymbol, OutputSymbol>) -> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+        match self.error_token {
+            Some(ref error_token)   => dfa.with_error_state(error_token.clone()),
+            None                    => dfa
+        }
+    }
+
+    ///
+    /// Adds the error token (if there is one) to a DFA prepared from this matcher, ending the error at any of the specified symbols
+    ///
+    fn add_error_token_stopping_at(&self, dfa: SymbolRangeDfa<InputSymbol, OutputSymbol>, stop_symbols: &[SymbolRange<InputSymbol>]) -> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+        match self.error_token {
+            Some(ref error_token)   => dfa.with_error_state_stopping_at(error_token.clone(), stop_symbols),
+            None                    => dfa
+        }
+    }
+
+    ///
+    /// Finds the pairs of patterns in this matcher that can both match the same string
+    ///
+    /// When this happens, only the lowest output symbol is ever produced for the strings that both patterns match. This is
+    /// often intended (eg, keywords taking priority over identifiers), but can also hide mistakes in a set of patterns, so
+    /// this can be used to review them. Pairs with the same output symbol are not reported, as they can't be ambiguous.
+    /// Anchored patterns are not checked.
+    ///
+    pub fn find_conflicts(&self) -> Vec<(OutputSymbol, OutputSymbol)> {
+        let mut conflicts = vec![];
+
+        for first in 0..self.patterns.len() {
+            for second in (first+1)..self.patterns.len() {
+                let (ref first_pattern, ref first_output)   = self.patterns[first];
+                let (ref second_pattern, ref second_output) = self.patterns[second];
+
+                if first_output != second_output && first_pattern.overlaps(second_pattern) {
+                    conflicts.push((first_output.clone(), second_output.clone()));
+                }
+            }
+        }
+
+        conflicts
+    }
+
     ///
     /// Compiles an NDFA from this TokenMatcher
     ///
     pub fn to_ndfa(&self) -> Box<StateMachine<SymbolRange<InputSymbol>, OutputSymbol>> {
-        let mut ndfa = Ndfa::new();
+        self.to_ndfa_with_anchors(&[])
+    }
 
-        for &(ref pattern, ref output) in &self.patterns {
+    ///
+    /// Compiles an NDFA from this TokenMatcher without removing any overlapping ranges
+    ///
+    /// This saves some work when the NDFA is going to be combined with others before it is compiled. The result is not
+    /// suitable for compiling into a DFA until `fix_overlapping_ranges()` has been called on it.
+    ///
+    pub fn to_ndfa_raw(&self) -> Ndfa<SymbolRange<InputSymbol>, OutputSymbol> {
+        self.build_ndfa(&[])
+    }
+
+    ///
+    /// Compiles an NDFA from the unanchored patterns in this TokenMatcher along with any anchored patterns using one of the specified anchors
+    ///
+    fn to_ndfa_with_anchors(&self, anchors: &[Anchor]) -> Box<StateMachine<SymbolRange<InputSymbol>, OutputSymbol>> {
+        let mut ndfa = self.build_ndfa(anchors);
+
+        // Clear out any overlapping ranges so we can build a valid DFA
+        ndfa.fix_overlapping_ranges();
+
+        Box::new(ndfa)
+    }
+
+    ///
+    /// Builds an NDFA that may contain overlapping ranges from the unanchored patterns in this TokenMatcher along with any
+    /// anchored patterns using one of the specified anchors
+    ///
+    fn build_ndfa(&self, anchors: &[Anchor]) -> Ndfa<SymbolRange<InputSymbol>, OutputSymbol> {
+        let anchored    = self.anchored_patterns.iter()
+            .filter(|&&(_, _, anchor)| anchors.contains(&anchor))
+            .map(|&(ref pattern, ref output, _)| (pattern, output));
+        let patterns: Vec<_> = self.patterns.iter()
+            .map(|&(ref pattern, ref output)| (pattern, output))
+            .chain(anchored)
+            .collect();
+
+        let states      = patterns.iter().fold(1, |total: usize, &(pattern, _)| total.saturating_add(pattern.estimated_states()));
+        let mut ndfa    = Ndfa::with_capacity(states);
+
+        for (pattern, output) in patterns {
             // Compile each pattern starting at state 0
             let end_state = pattern.compile(&mut ndfa, 0);
 
@@ -71,20 +213,168 @@ impl<InputSymbol: Clone+Ord+Countable+'static, OutputSymbol: Clone+Ord+'static>
             ndfa.set_output_symbol(end_state, output.clone());
         }
 
-        // Clear out any overlapping ranges so we can build a valid DFA
-        ndfa.fix_overlapping_ranges();
+        ndfa
+    }
 
-        Box::new(ndfa)
+    ///
+    /// Prepares a DFA for this TokenMatcher with the minimum possible number of states
+    ///
+    /// This is slower to generate than the DFA created by `prepare_to_match()` but can be considerably smaller, particularly
+    /// when matching large sets of keywords.
+    ///
+    pub fn prepare_to_match_minimized(&self) -> SymbolRangeDfa<InputSymbol, OutputSymbol> {
+        self.prepare_to_match().minimize()
+    }
+
+    ///
+    /// Prepares the DFAs needed to match the anchored patterns in this TokenMatcher, along with a DFA for the unanchored
+    /// patterns to use with them
+    ///
+    /// The error token is added to every DFA. An error token can only end after the start of the input, where the tokenizer
+    /// tries both the unanchored and the end-anchored patterns, so in all of them it stops at any symbol that can start
+    /// one of those patterns.
+    ///
+    fn prepare_anchored(&self) -> (SymbolRangeDfa<InputSymbol, OutputSymbol>, AnchoredDfas<InputSymbol, OutputSymbol>) {
+        let end             = self.to_ndfa_with_anchors(&[Anchor::End]).prepare_to_match();
+        let stop_symbols    = end.get_transitions_for_state(0).into_iter().map(|(range, _)| range).collect::<Vec<_>>();
+
+        let unanchored      = self.add_error_token_stopping_at(self.to_ndfa().prepare_to_match(), &stop_symbols);
+        let anchored        = AnchoredDfas {
+            start:          self.add_error_token_stopping_at(self.to_ndfa_with_anchors(&[Anchor::Start]).prepare_to_match(), &stop_symbols),
+            end:            self.add_error_token_stopping_at(end, &stop_symbols),
+            start_and_end:  self.add_error_token_stopping_at(self.to_ndfa_with_anchors(&[Anchor::Start, Anchor::End]).prepare_to_match(), &stop_symbols)
+        };
+
+        (unanchored, anchored)
     }
 }
 
+///
+/// The DFAs used by a tokenizer to match anchored patterns
+///
+struct AnchoredDfas<InputSymbol: Clone+Ord+Countable, OutputSymbol: Clone+Ord> {
+    /// Unanchored and start-anchored patterns (used at the start of the input)
+    start: SymbolRangeDfa<InputSymbol, OutputSymbol>,
+
+    /// Unanchored and end-anchored patterns
+    end: SymbolRangeDfa<InputSymbol, OutputSymbol>,
+
+    /// Unanchored, start-anchored and end-anchored patterns
+    start_and_end: SymbolRangeDfa<InputSymbol, OutputSymbol>
+}
+
 impl<'a, InputSymbol: Clone+Ord+Countable+'static, OutputSymbol: Clone+Ord+'static> PrepareToMatch<SymbolRangeDfa<InputSymbol, OutputSymbol>> 
 for &'a TokenMatcher<InputSymbol, OutputSymbol> {
     #[inline]
     fn prepare_to_match(self) -> SymbolRangeDfa<InputSymbol, OutputSymbol> {
         let ndfa = self.to_ndfa();
 
-        ndfa.prepare_to_match()
+        self.add_error_token(ndfa.prepare_to_match())
+    }
+}
+
+///
+/// Describes how the mode of a tokenizer changes after it produces a token
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ModeChange {
+    /// Switches to a new mode, remembering the current one so it can be returned to later
+    Push(usize),
+
+    /// Returns to the mode that was active before the last `Push`
+    Pop
+}
+
+///
+/// A set of token matchers for a tokenizer that can switch between different modes
+///
+/// Some languages can't be tokenized by a single set of patterns: for instance, digits inside a string literal shouldn't be
+/// produced as numbers. Each mode has its own set of patterns, and the tokenizer moves between them by following a stack
+/// of modes: producing a particular token in a particular mode can push a new mode onto the stack or pop back to the
+/// previous mode. Tokenizers using these modes are created with `Tokenizer::with_mode_stack()`, and start in mode 0.
+///
+pub struct TokenModes<InputSymbol: Clone+Ord+Countable, OutputSymbol: Clone+Ord> {
+    /// The DFA for each mode
+    dfas: Vec<SymbolRangeDfa<InputSymbol, OutputSymbol>>,
+
+    /// How the mode changes when a token is produced in a particular mode
+    changes: BTreeMap<(usize, OutputSymbol), ModeChange>
+}
+
+impl<InputSymbol: Clone+Ord+Countable+'static, OutputSymbol: Clone+Ord+'static> TokenModes<InputSymbol, OutputSymbol> {
+    ///
+    /// Creates a new set of modes (with no modes in it)
+    ///
+    pub fn new() -> TokenModes<InputSymbol, OutputSymbol> {
+        TokenModes { dfas: vec![], changes: BTreeMap::new() }
+    }
+
+    ///
+    /// Adds a mode that matches the patterns in a token matcher, returning its index
+    ///
+    pub fn add_mode(&mut self, matcher: &TokenMatcher<InputSymbol, OutputSymbol>) -> usize {
+        self.dfas.push(matcher.prepare_to_match());
+        self.dfas.len()-1
+    }
+
+    ///
+    /// Sets how the mode changes when a particular output symbol is produced while in a particular mode
+    ///
+    /// Both the mode and the target of a `Push` must already have been added. Popping when there's only one mode on the
+    /// stack leaves the tokenizer in that mode.
+    ///
+    pub fn on_token(&mut self, mode: usize, output: OutputSymbol, change: ModeChange) {
+        if mode >= self.dfas.len() {
+            panic!("Mode {} has not been added", mode);
+        }
+
+        if let ModeChange::Push(target_mode) = change {
+            if target_mode >= self.dfas.len() {
+                panic!("Mode {} has not been added", target_mode);
+            }
+        }
+
+        self.changes.insert((mode, output), change);
+    }
+}
+
+impl<InputSymbol: Clone+Ord+Countable+'static, OutputSymbol: Clone+Ord+'static> Default for TokenModes<InputSymbol, OutputSymbol> {
+    fn default() -> TokenModes<InputSymbol, OutputSymbol> {
+        TokenModes::new()
+    }
+}
+
+///
+/// The modes that a tokenizer has been through
+///
+struct ModeStack<'a, InputSymbol: Clone+Ord+Countable+'a, OutputSymbol: Clone+Ord+'a> {
+    /// The modes that the tokenizer can be in
+    modes: &'a TokenModes<InputSymbol, OutputSymbol>,
+
+    /// The stack of modes (the last entry is the current mode)
+    stack: Vec<usize>
+}
+
+impl<'a, InputSymbol: Clone+Ord+Countable+'a, OutputSymbol: Clone+Ord+'a> ModeStack<'a, InputSymbol, OutputSymbol> {
+    ///
+    /// Returns the DFA for the current mode
+    ///
+    #[inline]
+    fn current_dfa(&self) -> &'a SymbolRangeDfa<InputSymbol, OutputSymbol> {
+        &self.modes.dfas[*self.stack.last().unwrap()]
+    }
+
+    ///
+    /// Updates the mode after a token has been produced
+    ///
+    fn token_produced(&mut self, output: &OutputSymbol) {
+        let current_mode = *self.stack.last().unwrap();
+
+        match self.modes.changes.get(&(current_mode, output.clone())) {
+            Some(&ModeChange::Push(new_mode))               => self.stack.push(new_mode),
+            Some(&ModeChange::Pop) if self.stack.len() > 1  => { self.stack.pop(); },
+            _                                               => { }
+        }
     }
 }
 
@@ -121,6 +411,15 @@ pub struct Tokenizer<'a, InputSymbol: Clone+Ord+Countable+'a, OutputSymbol: Clon
 
     /// Tape of input symbols that will be used to generate the result
     tape: Tape<InputSymbol, Reader>,
+
+    /// DFAs used to match anchored patterns (None if this tokenizer doesn't support anchors)
+    anchored: Option<AnchoredDfas<InputSymbol, OutputSymbol>>,
+
+    /// The maximum number of symbols that can be read while matching a single token (None if there's no limit)
+    max_token_length: Option<usize>,
+
+    /// The modes for this tokenizer (None if it only has a single set of patterns)
+    modes: Option<ModeStack<'a, InputSymbol, OutputSymbol>>
 }
 
 impl<'a, InputSymbol: Clone+Ord+Countable, OutputSymbol: Clone+Ord+'static, Reader: SymbolReader<InputSymbol>> Tokenizer<'a, InputSymbol, OutputSymbol, Reader> {
@@ -128,14 +427,58 @@ impl<'a, InputSymbol: Clone+Ord+Countable, OutputSymbol: Clone+Ord+'static, Read
     /// Creates a new tokenizer from a pattern (usually a TokenMatcher)
     ///
     pub fn new<'b, Prepare: PrepareToMatch<SymbolRangeDfa<InputSymbol, OutputSymbol>>>(source: Reader, pattern: Prepare) -> Tokenizer<'b, InputSymbol, OutputSymbol, Reader> {
-        Tokenizer { dfa: Owned(pattern.prepare_to_match()), tape: Tape::new(source) }
+        Tokenizer { dfa: Owned(pattern.prepare_to_match()), tape: Tape::new(source), anchored: None, max_token_length: None, modes: None }
     }
 
     ///
     /// Creates a new tokenizer from a prepared pattern
     ///
     pub fn new_prepared<'b>(source: Reader, pattern: &'b SymbolRangeDfa<InputSymbol, OutputSymbol>) -> Tokenizer<'b, InputSymbol, OutputSymbol, Reader> {
-        Tokenizer { dfa: Reference(pattern), tape: Tape::new(source) }
+        Tokenizer { dfa: Reference(pattern), tape: Tape::new(source), anchored: None, max_token_length: None, modes: None }
+    }
+
+    ///
+    /// Creates a new tokenizer from a TokenMatcher that will also match its anchored patterns
+    ///
+    pub fn new_anchored<'b>(source: Reader, pattern: &TokenMatcher<InputSymbol, OutputSymbol>) -> Tokenizer<'b, InputSymbol, OutputSymbol, Reader>
+    where InputSymbol: 'static {
+        let (dfa, anchored) = pattern.prepare_anchored();
+
+        Tokenizer { dfa: Owned(dfa), tape: Tape::new(source), anchored: Some(anchored), max_token_length: None, modes: None }
+    }
+
+    ///
+    /// Creates a new tokenizer that switches between several sets of patterns, starting with mode 0
+    ///
+    /// After each token is produced, the mode changes set up in the `TokenModes` are used to decide which mode to use
+    /// for the next token. Skipped symbols don't affect the mode.
+    ///
+    pub fn with_mode_stack<'b>(source: Reader, modes: &'b TokenModes<InputSymbol, OutputSymbol>) -> Tokenizer<'b, InputSymbol, OutputSymbol, Reader> {
+        if modes.dfas.is_empty() {
+            panic!("A tokenizer needs at least one mode");
+        }
+
+        Tokenizer {
+            dfa:                Reference(&modes.dfas[0]),
+            tape:               Tape::new(source),
+            anchored:           None,
+            max_token_length:   None,
+            modes:              Some(ModeStack { modes: modes, stack: vec![0] })
+        }
+    }
+
+    ///
+    /// Sets the maximum number of symbols that will be read while trying to match a single token
+    ///
+    /// Normally the tokenizer keeps reading until the DFA can't match any more symbols, which for a pattern like `.+` can
+    /// mean reading the whole of the input into memory. With a maximum length set, tokens of up to `max` symbols can be
+    /// matched. If the DFA still hasn't reached a decision after reading `max + 1` symbols (the extra symbol is needed to
+    /// tell that a token of exactly `max` symbols has ended), the whole run of symbols that it would have matched is
+    /// rejected: no token is produced for any part of it, and the tokenizer carries on from the end of the run. The
+    /// tokenizer never buffers more than `max + 1` symbols.
+    ///
+    pub fn set_max_token_length(&mut self, max: usize) {
+        self.max_token_length = Some(max);
     }
 
     ///
@@ -145,6 +488,25 @@ impl<'a, InputSymbol: Clone+Ord+Countable, OutputSymbol: Clone+Ord+'static, Read
         self.tape.get_source_position()
     }
 
+    ///
+    /// Returns the number of symbols that have been read from the source reader
+    ///
+    /// The tokenizer may need to read ahead of the end of the last token to decide where it ends, so this can be greater than
+    /// `get_source_position()`.
+    ///
+    pub fn absolute_position(&self) -> usize {
+        self.tape.absolute_position()
+    }
+
+    ///
+    /// Consumes this tokenizer and returns the reader it was reading from
+    ///
+    /// The reader will continue from `absolute_position()`: any symbols that were read ahead of the last token are lost.
+    ///
+    pub fn into_source(self) -> Reader {
+        self.tape.into_inner()
+    }
+
     ///
     /// Skips an input symbol (returning the symbol that was skipped)
     ///
@@ -168,50 +530,223 @@ impl<'a, InputSymbol: Clone+Ord+Countable, OutputSymbol: Clone+Ord+'static, Read
     /// a new match at the next symbol. 
     ///
     pub fn next_token(&mut self) -> Option<(Range<usize>, OutputSymbol)> {
-        // Start of the next symbol
-        let start_pos = self.tape.get_source_position();
+        let token = self.match_token();
+
+        // Won't try to match anything before this position
+        self.tape.cut();
+
+        token
+    }
 
-        // Match against it
-        let match_result = match_pattern(self.dfa.get().start(), &mut self.tape);
+    ///
+    /// Reads the next token from the tokenizer, using a function to generate the token value from the output symbol and the
+    /// symbols that were matched
+    ///
+    /// This is useful when the value of a token depends on the text that was matched, for example when converting a string of
+    /// digits into a number. It otherwise behaves in the same way as `next_token`.
+    ///
+    pub fn next_token_mapped<TokenValue, MapFn: Fn(OutputSymbol, &[InputSymbol]) -> TokenValue>(&mut self, map_fn: MapFn) -> Option<(Range<usize>, TokenValue)> {
+        let token = self.match_token();
 
-        let end_pos = self.tape.get_source_position();
-        match match_result {
-            Accept(length, outputsymbol) => {
-                if length > 0 {
-                    // Rewind the tape to after the accepted symbol
-                    self.tape.rewind(end_pos-start_pos - length);
+        // The tape is cut at the start of the token, so the matched symbols are at the start of the buffer
+        let token = token.map(|(range, output_symbol)| {
+            let buffered    = self.tape.buffered_symbols();
+            let value       = map_fn(output_symbol, &buffered[0..range.len()]);
 
-                    // Won't try to match anything before this position
-                    self.tape.cut();
+            (range, value)
+        });
 
-                    // Result is the oputput symbol
-                    let match_range = start_pos..(start_pos+length);
-                    Some((match_range, outputsymbol.clone()))
-                } else {
-                    // Zero-length match
-                    // If we accepted matches of length 0 we'd get an infinite stream when we hit a symbol that doesn't match, so for these we just skip a single symbol
-                    self.tape.rewind(end_pos-start_pos);
+        self.tape.cut();
+
+        token
+    }
+
+    ///
+    /// Matches the next token, leaving the tape after the token and its symbols in the buffer
+    ///
+    fn match_token(&mut self) -> Option<(Range<usize>, OutputSymbol)> {
+        loop {
+            // Start of the next symbol
+            let start_pos   = self.tape.get_source_position();
+            let max_length  = self.max_token_length;
+
+            // Symbols before here have been matched or skipped, so don't need to be kept any more
+            self.tape.cut();
+
+            // End-anchored patterns can only be accepted if they reach the end of the reader (a run that's too long for
+            // these patterns might still be matched by the others, so it's treated as no match here)
+            if let Some(ref anchored) = self.anchored {
+                let end_dfa = if start_pos == 0 { &anchored.start_and_end } else { &anchored.end };
+
+                if let Ok(Some((length, output_symbol))) = longest_match(end_dfa, &mut self.tape, max_length) {
+                    if at_end_of_input(&mut self.tape) {
+                        return Some((start_pos..(start_pos+length), output_symbol));
+                    }
+
+                    // Didn't reach the end, so try again without the end-anchored patterns
+                    self.tape.rewind(length);
+                }
+            }
+
+            // Start-anchored patterns are only matched at the start of the input
+            let dfa = match (&self.anchored, &self.modes) {
+                (&Some(ref anchored), _) if start_pos == 0  => &anchored.start,
+                (_, &Some(ref modes))                       => modes.current_dfa(),
+                _                                           => self.dfa.get()
+            };
+
+            let token = match longest_match(dfa, &mut self.tape, max_length) {
+                Ok(token)           => token.map(|(length, output_symbol)| (start_pos..(start_pos+length), output_symbol)),
+                Err(TokenTooLong)   => {
+                    // Reject the whole of the over-long run rather than matching part of it, and try again after it
+                    if let Some(max_length) = max_length {
+                        skip_run(dfa, &mut self.tape, max_length);
+                    }
+
+                    continue;
+                }
+            };
+
+            // The token can change the mode used to match the next token
+            if let (&mut Some(ref mut modes), &Some((_, ref output_symbol))) = (&mut self.modes, &token) {
+                modes.token_produced(output_symbol);
+            }
+
+            return token;
+        }
+    }
+}
+
+///
+/// Indicates that a DFA read more symbols than the maximum token length without reaching a decision
+///
+struct TokenTooLong;
 
-                    // Return no match
-                    None
+///
+/// Matches a DFA against a tape, returning the length and output symbol of the longest non-empty match
+///
+/// The tape is left after the matched symbols, or where it started if there is no match. If a maximum length is supplied and
+/// the DFA hasn't finished after reading that many symbols plus one more symbol of lookahead (which is needed to decide that
+/// a token of exactly the maximum length has ended), the tape is left where it started and `TokenTooLong` is returned.
+///
+fn longest_match<InputSymbol: Clone+Ord+Countable, OutputSymbol: Clone+Ord+'static, Reader: SymbolReader<InputSymbol>>(dfa: &SymbolRangeDfa<InputSymbol, OutputSymbol>, tape: &mut Tape<InputSymbol, Reader>, max_length: Option<usize>) -> Result<Option<(usize, OutputSymbol)>, TokenTooLong> {
+    let start_pos = tape.get_source_position();
+
+    // Match against it
+    let match_result = match max_length {
+        None                => match_pattern(dfa.start(), tape),
+        Some(max_length)    => {
+            let mut current_state = dfa.start();
+
+            while let More(this_state) = current_state {
+                // Give up if we've read too many symbols without reaching a decision (the symbol after a token of the
+                // maximum length may be needed to finish it, so one more symbol than the maximum can be read)
+                if tape.get_source_position() - start_pos > max_length {
+                    let end_pos = tape.get_source_position();
+                    tape.rewind(end_pos-start_pos);
+
+                    return Err(TokenTooLong);
                 }
-            },
 
-            Reject => {
-                // Rewind back to the start position
-                self.tape.rewind(end_pos-start_pos);
+                current_state = if let Some(next_symbol) = tape.next_symbol() {
+                    this_state.next(next_symbol)
+                } else {
+                    this_state.finish()
+                };
+            }
+
+            current_state
+        }
+    };
+
+    let end_pos = tape.get_source_position();
+    match match_result {
+        Accept(length, outputsymbol) => {
+            if length > 0 {
+                // Rewind the tape to after the accepted symbol
+                tape.rewind(end_pos-start_pos - length);
 
-                // No match
-                None
-            },
+                // Result is the oputput symbol
+                Ok(Some((length, outputsymbol.clone())))
+            } else {
+                // Zero-length match
+                // If we accepted matches of length 0 we'd get an infinite stream when we hit a symbol that doesn't match, so for these we just skip a single symbol
+                tape.rewind(end_pos-start_pos);
 
-            _ => {
-                panic!("Unexpected output state from state machine");
+                // Return no match
+                Ok(None)
             }
+        },
+
+        Reject => {
+            // Rewind back to the start position
+            tape.rewind(end_pos-start_pos);
+
+            // No match
+            Ok(None)
+        },
+
+        _ => {
+            panic!("Unexpected output state from state machine");
         }
     }
 }
 
+///
+/// Moves a tape past the run of symbols that a DFA matches before it reaches a decision, without buffering all of them
+///
+/// The run ends after the last accepting state the DFA passed through, or before the symbol it stopped at if there was no
+/// accepting state. Only the last `max_length + 1` symbols are kept on the tape, so if the last accepting state is further
+/// back than that, the run ends at the oldest symbol that is still kept. The tape always moves forward by at least one
+/// symbol, so this should only be used when the DFA has already been found to read more than one symbol.
+///
+fn skip_run<InputSymbol: Clone+Ord+Countable, OutputSymbol: Clone+Ord+'static, Reader: SymbolReader<InputSymbol>>(dfa: &SymbolRangeDfa<InputSymbol, OutputSymbol>, tape: &mut Tape<InputSymbol, Reader>, max_length: usize) {
+    let start_pos           = tape.get_source_position();
+    let mut cut_pos         = start_pos;
+    let mut at_end          = false;
+    let mut current_state   = dfa.start();
+
+    while let More(this_state) = current_state {
+        // Keep the buffer to the same size it would be when matching a token
+        if tape.buffer_size() > max_length {
+            tape.cut();
+            cut_pos = tape.get_source_position();
+        }
+
+        current_state = if let Some(next_symbol) = tape.next_symbol() {
+            this_state.next(next_symbol)
+        } else {
+            at_end = true;
+            this_state.finish()
+        };
+    }
+
+    // The symbol the DFA stopped at isn't part of the run, unless the DFA stopped because the input ended
+    let end_pos = tape.get_source_position();
+    let run_end = match current_state {
+        Accept(length, _)   => start_pos + length,
+        _                   => if at_end { end_pos } else { end_pos - 1 }
+    };
+
+    // The first symbol is always part of the run, and symbols before the last cut can't be rewound to
+    let run_end = run_end.max(start_pos + 1).max(cut_pos);
+    tape.rewind(end_pos - run_end);
+}
+
+///
+/// True if there are no more symbols to read from a tape (reading ahead by a symbol if necessary)
+///
+fn at_end_of_input<InputSymbol: Clone, Reader: SymbolReader<InputSymbol>>(tape: &mut Tape<InputSymbol, Reader>) -> bool {
+    if tape.at_end_of_reader() {
+        true
+    } else if tape.next_symbol().is_none() {
+        true
+    } else {
+        tape.rewind(1);
+        false
+    }
+}
+
 impl<'a, InputSymbol: Clone+Ord+Countable, OutputSymbol: Clone+Ord+'static, Reader: SymbolReader<InputSymbol>> SymbolReader<OutputSymbol> for Tokenizer<'a, InputSymbol, OutputSymbol, Reader> {
     #[inline]
     fn next_symbol(&mut self) -> Option<OutputSymbol> {
@@ -270,6 +805,234 @@ mod test {
         assert!(matches("bbaaa", &token_matcher) == Some(2));
     }
 
+    #[test]
+    fn can_recover_source_after_tokenizing() {
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone)]
+        enum TestToken {
+            Digits
+        }
+
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), TestToken::Digits);
+
+        let mut tokenizer = Tokenizer::new("123;rest".read_symbols(), &token_matcher);
+
+        assert!(tokenizer.next_token().map(|(range, _)| range) == Some(0..3));
+        assert!(tokenizer.get_source_position() == 3);
+        assert!(tokenizer.absolute_position() == 4);
+
+        // The ';' was read to find the end of the digits, so the source continues after it
+        let mut source = tokenizer.into_source();
+        assert!(source.to_vec() == vec!['r', 'e', 's', 't']);
+    }
+
+    #[test]
+    fn can_extend_token_matcher() {
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+        enum TestToken {
+            Keyword,
+            Operator
+        }
+
+        let mut keywords = TokenMatcher::new();
+        keywords.add_pattern(exactly("if"), TestToken::Keyword);
+        keywords.add_pattern(exactly("else"), TestToken::Keyword);
+
+        let mut operators = TokenMatcher::new();
+        operators.add_pattern(exactly("+"), TestToken::Operator);
+        operators.add_pattern(exactly("=="), TestToken::Operator);
+
+        keywords.extend(operators);
+
+        let tokenizer       = Tokenizer::new("if==else+".read_symbols(), &keywords);
+        let tokens: Vec<_>  = tokenizer.collect();
+
+        assert!(tokens == vec![
+            (0..2, TestToken::Keyword), 
+            (2..4, TestToken::Operator), 
+            (4..8, TestToken::Keyword), 
+            (8..9, TestToken::Operator)
+        ]);
+    }
+
+    #[test]
+    fn start_anchored_pattern_only_matches_first_token() {
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+        enum TestToken {
+            Heading,
+            Word,
+            Whitespace
+        }
+
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_anchored_pattern(exactly("#"), TestToken::Heading, Anchor::Start);
+        token_matcher.add_pattern(MatchRange('a', 'z').repeat_forever(1), TestToken::Word);
+        token_matcher.add_pattern(exactly(" ").repeat_forever(1), TestToken::Whitespace);
+
+        let tokenizer       = Tokenizer::new_anchored("#ab #cd".read_symbols(), &token_matcher);
+        let tokens: Vec<_>  = tokenizer.collect();
+
+        assert!(tokens == vec![
+            (0..1, TestToken::Heading),
+            (1..3, TestToken::Word),
+            (3..4, TestToken::Whitespace),
+            (5..7, TestToken::Word)
+        ]);
+    }
+
+    #[test]
+    fn end_anchored_pattern_only_matches_last_token() {
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+        enum TestToken {
+            LastWord,
+            Word,
+            Whitespace
+        }
+
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_anchored_pattern(MatchRange('a', 'z').repeat_forever(1), TestToken::LastWord, Anchor::End);
+        token_matcher.add_pattern(MatchRange('a', 'z').repeat_forever(1), TestToken::Word);
+        token_matcher.add_pattern(exactly(" ").repeat_forever(1), TestToken::Whitespace);
+
+        let tokenizer       = Tokenizer::new_anchored("ab cd ef".read_symbols(), &token_matcher);
+        let tokens: Vec<_>  = tokenizer.collect();
+
+        assert!(tokens == vec![
+            (0..2, TestToken::Word),
+            (2..3, TestToken::Whitespace),
+            (3..5, TestToken::Word),
+            (5..6, TestToken::Whitespace),
+            (6..8, TestToken::LastWord)
+        ]);
+    }
+
+    #[test]
+    fn anchored_patterns_are_ignored_by_unanchored_tokenizer() {
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+        enum TestToken {
+            Heading,
+            Word
+        }
+
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_anchored_pattern(exactly("#"), TestToken::Heading, Anchor::Start);
+        token_matcher.add_pattern(MatchRange('a', 'z').repeat_forever(1), TestToken::Word);
+
+        let tokenizer       = Tokenizer::new("#ab".read_symbols(), &token_matcher);
+        let tokens: Vec<_>  = tokenizer.collect();
+
+        assert!(tokens == vec![(1..3, TestToken::Word)]);
+    }
+
+    #[test]
+    fn max_token_length_bounds_buffer() {
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+        enum TestToken {
+            Anything,
+            Semicolon
+        }
+
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('a', 'z').repeat_forever(1).append(";"), TestToken::Anything);
+        token_matcher.add_pattern(exactly(";"), TestToken::Semicolon);
+
+        let input           = format!("{}; ab;", "x".repeat(1000));
+        let mut tokenizer   = Tokenizer::new(input.read_symbols(), &token_matcher);
+        tokenizer.set_max_token_length(16);
+
+        let mut tokens = vec![];
+        while !tokenizer.at_end_of_reader() {
+            if let Some(token) = tokenizer.next_token() {
+                tokens.push(token);
+            } else {
+                tokenizer.skip_input();
+            }
+
+            // The limit allows one symbol of lookahead after a token of the maximum length
+            assert!(tokenizer.tape.buffer_size() <= 16 + 1);
+        }
+
+        // The long run of 'x's can't be matched without going over the limit, so none of it is produced as a token
+        assert!(tokens == vec![(1002..1005, TestToken::Anything)]);
+    }
+
+    #[test]
+    fn token_of_exactly_max_token_length_is_matched() {
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), 0);
+
+        let mut at_end = Tokenizer::new("1234".read_symbols(), &token_matcher);
+        at_end.set_max_token_length(4);
+
+        assert!(at_end.next_token() == Some((0..4, 0)));
+
+        let mut before_space = Tokenizer::new("1234 56789".read_symbols(), &token_matcher);
+        before_space.set_max_token_length(4);
+
+        assert!(before_space.next_token() == Some((0..4, 0)));
+        before_space.skip_input();
+        assert!(before_space.next_token() == None);
+    }
+
+    #[test]
+    fn buffer_can_hold_one_more_symbol_than_max_token_length() {
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), 0);
+
+        let mut tokenizer = Tokenizer::new("1234 ".read_symbols(), &token_matcher);
+        tokenizer.set_max_token_length(4);
+
+        // The space after the token has to be read to tell that it's finished
+        assert!(tokenizer.match_token() == Some((0..4, 0)));
+        assert!(tokenizer.tape.buffer_size() == 4 + 1);
+    }
+
+    #[test]
+    fn over_long_run_is_rejected_whole() {
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), 0);
+        token_matcher.add_pattern(MatchRange('a', 'z').repeat_forever(1), 1);
+
+        let mut tokenizer = Tokenizer::new("123456789ab 12".read_symbols(), &token_matcher);
+        tokenizer.set_max_token_length(4);
+
+        let tokens: Vec<_> = tokenizer.collect();
+
+        assert!(tokens == vec![(9..11, 1), (12..14, 0)]);
+    }
+
+    #[test]
+    fn over_long_error_run_is_rejected_whole() {
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), 0);
+        token_matcher.set_error_token(9);
+
+        let mut tokenizer = Tokenizer::new("??????1?".read_symbols(), &token_matcher);
+        tokenizer.set_max_token_length(4);
+
+        let tokens: Vec<_> = tokenizer.collect();
+
+        assert!(tokens == vec![(6..7, 0), (7..8, 9)]);
+    }
+
+    #[test]
+    fn short_tokens_are_unaffected_by_max_token_length() {
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+        enum TestToken {
+            Digit
+        }
+
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), TestToken::Digit);
+
+        let mut tokenizer = Tokenizer::new("12 390  32".read_symbols(), &token_matcher);
+        tokenizer.set_max_token_length(4);
+
+        let tokens: Vec<_> = tokenizer.collect();
+
+        assert!(tokens == vec![(0..2, TestToken::Digit), (3..6, TestToken::Digit), (8..10, TestToken::Digit)]);
+    }
+
     #[test]
     fn can_distinguish_simple_tokens() {
         #[derive(Ord, PartialOrd, Eq, PartialEq, Clone)]
@@ -325,6 +1088,52 @@ mod test {
         assert!(match_pattern(matcher.start(), &mut "abbbb".read_symbols()).is_accepted(&TestToken::Abbb));
     }
 
+    #[test]
+    fn minimized_keywords_have_fewer_states() {
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+        enum TestToken {
+            Keyword,
+            Identifier
+        }
+
+        let mut keywords = vec![];
+        for prefix in "abcdefghij".chars() {
+            for suffix in vec!["one", "two", "six", "ten", "red"] {
+                keywords.push(format!("{}{}", prefix, suffix));
+            }
+        }
+        assert!(keywords.len() == 50);
+
+        let mut token_matcher = TokenMatcher::new();
+        for keyword in keywords.iter() {
+            token_matcher.add_pattern(keyword.chars().collect::<Vec<char>>(), TestToken::Keyword);
+        }
+        token_matcher.add_pattern(MatchRange('a', 'z').repeat_forever(1).append("!"), TestToken::Identifier);
+
+        let unminimized = token_matcher.prepare_to_match();
+        let minimized   = token_matcher.prepare_to_match_minimized();
+
+        assert!(minimized.count_states() < unminimized.count_states());
+
+        for keyword in keywords.iter() {
+            assert!(match_pattern(unminimized.start(), &mut keyword.read_symbols()).is_accepted(&TestToken::Keyword));
+            assert!(match_pattern(minimized.start(), &mut keyword.read_symbols()).is_accepted(&TestToken::Keyword));
+        }
+
+        for input in vec!["atwo!", "kone", "aon", "zzz!", "atw", ""] {
+            let unminimized_result  = match_pattern(unminimized.start(), &mut input.read_symbols());
+            let minimized_result    = match_pattern(minimized.start(), &mut input.read_symbols());
+
+            match (unminimized_result, minimized_result) {
+                (Accept(len_a, sym_a), Accept(len_b, sym_b))    => assert!(len_a == len_b && sym_a == sym_b),
+                (Reject, Reject)                                => { },
+                _                                               => assert!(false)
+            }
+        }
+
+        assert!(match_pattern(minimized.start(), &mut "abc!".read_symbols()).is_accepted(&TestToken::Identifier));
+    }
+
     #[test]
     fn can_match_number_stream() {
         #[derive(Ord, PartialOrd, Eq, PartialEq, Clone)]
@@ -511,4 +1320,219 @@ mod test {
         assert!(tokenizer.next_symbol() == None);
         assert!(tokenizer.at_end_of_reader());
     }
+
+    #[test]
+    fn can_map_tokens_using_matched_symbols() {
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone)]
+        enum TestToken {
+            Number,
+            Whitespace
+        }
+
+        #[derive(PartialEq)]
+        enum TokenValue {
+            Number(u32),
+            Whitespace
+        }
+
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), TestToken::Number);
+        token_matcher.add_pattern(exactly(" ").repeat_forever(1), TestToken::Whitespace);
+
+        let mut tokenizer   = Tokenizer::new("12 345  6789".read_symbols(), &token_matcher);
+        let mut tokens      = vec![];
+
+        while let Some(token) = tokenizer.next_token_mapped(|output, symbols| {
+            match output {
+                TestToken::Number       => TokenValue::Number(symbols.iter().collect::<String>().parse().unwrap()),
+                TestToken::Whitespace   => TokenValue::Whitespace
+            }
+        }) {
+            tokens.push(token);
+        }
+
+        assert!(tokens == vec![
+            (0..2, TokenValue::Number(12)),
+            (2..3, TokenValue::Whitespace),
+            (3..6, TokenValue::Number(345)),
+            (6..8, TokenValue::Whitespace),
+            (8..12, TokenValue::Number(6789))
+        ]);
+    }
+
+    #[test]
+    fn keyword_and_identifier_conflict() {
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+        enum TestToken {
+            If,
+            Identifier,
+            Number
+        }
+
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(exactly("if"), TestToken::If);
+        token_matcher.add_pattern(MatchRange('a', 'z').repeat_forever(1), TestToken::Identifier);
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), TestToken::Number);
+
+        assert!(token_matcher.find_conflicts() == vec![(TestToken::If, TestToken::Identifier)]);
+    }
+
+    #[test]
+    fn disjoint_patterns_do_not_conflict() {
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('a', 'z').repeat_forever(1), 0);
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), 1);
+        token_matcher.add_pattern(exactly("abc"), 0);
+
+        assert!(token_matcher.find_conflicts().is_empty());
+    }
+
+    #[test]
+    fn raw_ndfa_compiles_same_as_to_ndfa_after_fixing_ranges() {
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(exactly("if"), 0);
+        token_matcher.add_pattern(MatchRange('a', 'z').repeat_forever(1), 1);
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), 2);
+
+        let mut raw_ndfa = token_matcher.to_ndfa_raw();
+        raw_ndfa.fix_overlapping_ranges();
+
+        let from_raw    = DfaCompiler::build(raw_ndfa, SymbolRangeDfaBuilder::new());
+        let from_ndfa   = token_matcher.to_ndfa().prepare_to_match();
+
+        assert!(from_raw == from_ndfa);
+
+        for input in vec!["if", "iffy", "x", "123", "-"] {
+            assert!(matches_prepared(input, &from_raw) == matches_prepared(input, &from_ndfa));
+        }
+    }
+
+    #[test]
+    fn error_token_spans_unmatched_input() {
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+        enum TestToken {
+            Number,
+            Whitespace,
+            Error
+        }
+
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), TestToken::Number);
+        token_matcher.add_pattern(exactly(" ").repeat_forever(1), TestToken::Whitespace);
+        token_matcher.set_error_token(TestToken::Error);
+
+        let mut tokenizer   = Tokenizer::new("12 ??? 34".read_symbols(), &token_matcher);
+        let mut tokens      = vec![];
+
+        while let Some(token) = tokenizer.next_token() {
+            tokens.push(token);
+        }
+
+        assert!(tokenizer.at_end_of_reader());
+        assert!(tokens == vec![
+            (0..2, TestToken::Number),
+            (2..3, TestToken::Whitespace),
+            (3..6, TestToken::Error),
+            (6..7, TestToken::Whitespace),
+            (7..9, TestToken::Number)
+        ]);
+    }
+
+    #[test]
+    fn error_token_at_start_and_end_of_input() {
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), 0);
+        token_matcher.set_error_token(1);
+
+        let tokens: Vec<_> = Tokenizer::new("ab12cd".read_symbols(), &token_matcher).collect();
+
+        assert!(tokens == vec![(0..2, 1), (2..4, 0), (4..6, 1)]);
+    }
+
+    #[test]
+    fn error_token_stops_at_end_anchored_pattern() {
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), 0);
+        token_matcher.add_anchored_pattern(exactly("!"), 1, Anchor::End);
+        token_matcher.set_error_token(9);
+
+        let tokens: Vec<_> = Tokenizer::new_anchored("12??!".read_symbols(), &token_matcher).collect();
+
+        assert!(tokens == vec![(0..2, 0), (2..4, 9), (4..5, 1)]);
+    }
+
+    #[test]
+    fn error_token_is_used_with_every_anchor() {
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), 0);
+        token_matcher.add_anchored_pattern(exactly("#"), 1, Anchor::Start);
+        token_matcher.add_anchored_pattern(exactly("!"), 2, Anchor::End);
+        token_matcher.set_error_token(9);
+
+        // Error tokens at the start of the input, before an end-anchored pattern and running to the end of the input
+        assert!(Tokenizer::new_anchored("??".read_symbols(), &token_matcher).collect::<Vec<_>>() == vec![(0..2, 9)]);
+        assert!(Tokenizer::new_anchored("?#1!".read_symbols(), &token_matcher).collect::<Vec<_>>() == vec![(0..2, 9), (2..3, 0), (3..4, 2)]);
+        assert!(Tokenizer::new_anchored("#?!".read_symbols(), &token_matcher).collect::<Vec<_>>() == vec![(0..1, 1), (1..2, 9), (2..3, 2)]);
+        assert!(Tokenizer::new_anchored("1!?".read_symbols(), &token_matcher).collect::<Vec<_>>() == vec![(0..1, 0), (1..3, 9)]);
+    }
+
+    #[test]
+    fn without_error_token_unmatched_input_is_not_a_token() {
+        let mut token_matcher = TokenMatcher::new();
+        token_matcher.add_pattern(MatchRange('0', '9').repeat_forever(1), 0);
+
+        let mut tokenizer = Tokenizer::new("ab12".read_symbols(), &token_matcher);
+
+        assert!(tokenizer.next_token().is_none());
+    }
+
+    #[test]
+    fn mode_stack_switches_patterns() {
+        #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+        enum TestToken {
+            Number,
+            Quote,
+            StringContent
+        }
+
+        let mut normal = TokenMatcher::new();
+        normal.add_pattern(MatchRange('0', '9').repeat_forever(1), TestToken::Number);
+        normal.add_pattern(exactly("\""), TestToken::Quote);
+
+        let mut string = TokenMatcher::new();
+        string.add_pattern(exactly("\""), TestToken::Quote);
+        string.add_pattern(MatchRange(' ', '!').or(MatchRange('#', '~')).repeat_forever(1), TestToken::StringContent);
+
+        let mut modes       = TokenModes::new();
+        let normal_mode     = modes.add_mode(&normal);
+        let string_mode     = modes.add_mode(&string);
+        modes.on_token(normal_mode, TestToken::Quote, ModeChange::Push(string_mode));
+        modes.on_token(string_mode, TestToken::Quote, ModeChange::Pop);
+
+        let tokenizer      = Tokenizer::with_mode_stack("12\"ab 34\"56".read_symbols(), &modes);
+        let tokens: Vec<_> = tokenizer.collect();
+
+        assert!(tokens == vec![
+            (0..2, TestToken::Number),
+            (2..3, TestToken::Quote),
+            (3..8, TestToken::StringContent),
+            (8..9, TestToken::Quote),
+            (9..11, TestToken::Number)
+        ]);
+    }
+
+    #[test]
+    fn popping_last_mode_stays_in_first_mode() {
+        let mut matcher = TokenMatcher::new();
+        matcher.add_pattern(exactly("a"), 0);
+        matcher.add_pattern(exactly("b"), 1);
+
+        let mut modes   = TokenModes::new();
+        let only_mode   = modes.add_mode(&matcher);
+        modes.on_token(only_mode, 1, ModeChange::Pop);
+
+        let tokens: Vec<_> = Tokenizer::with_mode_stack("abba".read_symbols(), &modes).map(|(_, output)| output).collect();
+
+        assert!(tokens == vec![0, 1, 1, 0]);
+    }
 }